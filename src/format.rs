@@ -0,0 +1,149 @@
+use color_eyre::{eyre::WrapErr, Result};
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+mod asciicast;
+mod compress;
+mod script;
+mod ttyrec;
+
+pub use asciicast::StreamWriter as AsciicastWriter;
+pub use compress::{read_file, Compression, Encoder};
+
+/// File formats vtyrec knows how to read and write
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Ttyrec,
+    AsciicastV1,
+    AsciicastV2,
+    /// util-linux `script`, the timing file sits next to the typescript
+    Script,
+}
+impl Format {
+    /// `.cast` is asciicast v2, `.json` is asciicast v1, `typescript` or
+    /// `.typescript` is `script`, anything else is ttyrec. `.gz` and `.zst`
+    /// are looked through.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let path = Compression::strip(path.as_ref());
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("cast") => Self::AsciicastV2,
+            Some("json") => Self::AsciicastV1,
+            Some("typescript") => Self::Script,
+            _ if path.file_name().is_some_and(|name| name == "typescript") => Self::Script,
+            _ => Self::Ttyrec,
+        }
+    }
+}
+
+/// Default timing file of a typescript, `<typescript>.timing`
+pub fn timing_path(typescript: impl AsRef<Path>) -> std::path::PathBuf {
+    let mut path = typescript.as_ref().as_os_str().to_owned();
+    path.push(".timing");
+    path.into()
+}
+
+/// (rows, cols) used when the recording doesn't know the terminal size
+pub const DEFAULT_SIZE: (u16, u16) = (24, 80);
+
+#[derive(Clone, Default)]
+pub struct Header {
+    /// (rows, cols), ttyrec doesn't store it
+    pub size: Option<(u16, u16)>,
+    /// unix timestamp of the beginning of the recording
+    pub timestamp: Option<u64>,
+    pub title: Option<String>,
+    pub command: Option<String>,
+    pub env: BTreeMap<String, String>,
+    /// exit code of the recorded command, `128 + signal` if it got killed
+    pub exit_status: Option<i32>,
+}
+
+#[derive(Clone)]
+pub enum Event {
+    Output(Vec<u8>),
+    /// (rows, cols)
+    Resize(u16, u16),
+}
+impl Event {
+    /// Render the event as bytes written to the terminal
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Event::Output(data) => data.clone(),
+            Event::Resize(rows, cols) => format!("\x1b[8;{rows};{cols}t").into_bytes(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Frame {
+    /// offset from the beginning of the recording
+    pub time: Duration,
+    pub event: Event,
+}
+
+#[derive(Clone, Default)]
+pub struct Recording {
+    pub header: Header,
+    pub frames: Vec<Frame>,
+}
+impl Recording {
+    pub fn load(path: impl AsRef<Path>, format: Format) -> Result<Self> {
+        Self::load_with_timing(&path, format, timing_path(&path))
+    }
+    /// `timing` is only used by [`Format::Script`]
+    pub fn load_with_timing(
+        path: impl AsRef<Path>,
+        format: Format,
+        timing: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = read_file(path)?;
+        match format {
+            Format::Ttyrec => Ok(ttyrec::read(&bytes)),
+            Format::AsciicastV1 | Format::AsciicastV2 => asciicast::read(&bytes),
+            Format::Script => read_file(timing)
+                .map(|timing| String::from_utf8_lossy(&timing).into_owned())
+                .map_err(Into::into)
+                .and_then(|timing| script::read(&bytes, &timing)),
+        }
+        .wrap_err_with(|| format!("Failed to read `{}`", path.display()))
+    }
+    pub fn save(&self, path: impl AsRef<Path>, format: Format) -> Result<()> {
+        self.save_with_timing(&path, format, timing_path(&path))
+    }
+    /// `timing` is only used by [`Format::Script`]. Both get compressed if
+    /// their extension says so.
+    pub fn save_with_timing(
+        &self,
+        path: impl AsRef<Path>,
+        format: Format,
+        timing: impl AsRef<Path>,
+    ) -> Result<()> {
+        use std::io::{BufWriter, Write};
+        let create = |path: &Path| -> Result<_> {
+            let file = std::fs::File::create(path)?;
+            Ok(BufWriter::new(Encoder::new(
+                file,
+                Compression::from_path(path),
+            )?))
+        };
+        let path = path.as_ref();
+        let mut writer = create(path)?;
+        match format {
+            Format::Ttyrec => ttyrec::write(self, &mut writer)?,
+            Format::AsciicastV1 => asciicast::write_v1(self, &mut writer)?,
+            Format::AsciicastV2 => asciicast::write_v2(self, &mut writer)?,
+            Format::Script => {
+                let mut timing = create(timing.as_ref())?;
+                script::write(self, &mut writer, &mut timing)?;
+                timing.flush()?;
+                timing.get_mut().finish()?;
+            }
+        }
+        writer.flush()?;
+        writer.get_mut().finish()?;
+        Ok(())
+    }
+    pub fn duration(&self) -> Duration {
+        self.frames.last().map(|f| f.time).unwrap_or_default()
+    }
+}
@@ -0,0 +1,263 @@
+use super::{Event, Frame, Header, Recording, DEFAULT_SIZE};
+use color_eyre::{eyre::bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+#[derive(Serialize, Deserialize)]
+struct V1 {
+    version: u8,
+    width: u16,
+    height: u16,
+    #[serde(default)]
+    duration: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// (delay since previous frame, data)
+    stdout: Vec<(f64, String)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct V2 {
+    version: u8,
+    width: u16,
+    height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+}
+
+/// Both v1 and v2 are accepted, the version is taken from the content
+pub fn read(bytes: &[u8]) -> Result<Recording> {
+    let text = std::str::from_utf8(bytes)?;
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let Some(first) = lines.next() else {
+        bail!("empty asciicast file");
+    };
+    match serde_json::from_str::<V2>(first) {
+        Ok(v2) if v2.version == 2 => read_v2(v2, lines),
+        // v1 is a single json object, which may span multiple lines
+        _ => read_v1(serde_json::from_str(text)?),
+    }
+}
+
+fn read_v1(v1: V1) -> Result<Recording> {
+    if v1.version != 1 {
+        bail!("unsupported asciicast version {}", v1.version);
+    }
+    let mut time = Duration::ZERO;
+    let frames = v1
+        .stdout
+        .into_iter()
+        .map(|(delay, data)| {
+            time += Duration::from_secs_f64(delay.max(0.0));
+            Frame {
+                time,
+                event: Event::Output(data.into_bytes()),
+            }
+        })
+        .collect();
+    Ok(Recording {
+        header: Header {
+            size: Some((v1.height, v1.width)),
+            timestamp: None,
+            title: v1.title,
+            command: v1.command,
+            env: v1.env,
+            exit_status: None,
+        },
+        frames,
+    })
+}
+
+fn read_v2<'a>(v2: V2, lines: impl Iterator<Item = &'a str>) -> Result<Recording> {
+    let mut frames = Vec::new();
+    let mut exit_status = None;
+    for line in lines {
+        let (time, kind, data): (f64, String, String) = serde_json::from_str(line)?;
+        let time = Duration::from_secs_f64(time.max(0.0));
+        let event = match kind.as_str() {
+            "o" => Event::Output(data.into_bytes()),
+            "r" => match data.split_once('x').map(|(c, r)| (r.parse(), c.parse())) {
+                Some((Ok(rows), Ok(cols))) => Event::Resize(rows, cols),
+                _ => bail!("invalid resize event `{data}`"),
+            },
+            // from asciicast v3, vtyrec writes it into v2 as well
+            "x" => {
+                exit_status = data.parse().ok();
+                continue;
+            }
+            // input and marker events don't affect the screen
+            _ => continue,
+        };
+        frames.push(Frame { time, event });
+    }
+    Ok(Recording {
+        header: Header {
+            size: Some((v2.height, v2.width)),
+            timestamp: v2.timestamp,
+            title: v2.title,
+            command: v2.command,
+            env: v2.env,
+            exit_status,
+        },
+        frames,
+    })
+}
+
+pub fn write_v1(rec: &Recording, writer: &mut impl std::io::Write) -> Result<()> {
+    let (height, width) = rec.header.size.unwrap_or(DEFAULT_SIZE);
+    let mut prev = Duration::ZERO;
+    let mut utf8 = Utf8Buffer::default();
+    let stdout = rec
+        .frames
+        .iter()
+        .map(|frame| {
+            let delay = frame.time.saturating_sub(prev).as_secs_f64();
+            prev = frame.time;
+            (delay, utf8.push(&frame.event.to_bytes()))
+        })
+        .collect();
+    let v1 = V1 {
+        version: 1,
+        width,
+        height,
+        duration: rec.duration().as_secs_f64(),
+        command: rec.header.command.clone(),
+        title: rec.header.title.clone(),
+        env: rec.header.env.clone(),
+        stdout,
+    };
+    serde_json::to_writer(&mut *writer, &v1)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+pub fn write_v2(rec: &Recording, writer: &mut impl std::io::Write) -> Result<()> {
+    write_v2_header(writer, &rec.header)?;
+    let mut utf8 = Utf8Buffer::default();
+    for frame in &rec.frames {
+        write_v2_event(writer, frame.time, &frame.event, &mut utf8)?;
+    }
+    if let Some(code) = rec.header.exit_status {
+        write_v2_exit(writer, rec.duration(), code)?;
+    }
+    Ok(())
+}
+
+fn write_v2_header(writer: &mut impl std::io::Write, header: &Header) -> Result<()> {
+    let (height, width) = header.size.unwrap_or(DEFAULT_SIZE);
+    let v2 = V2 {
+        version: 2,
+        width,
+        height,
+        timestamp: header.timestamp,
+        duration: None,
+        command: header.command.clone(),
+        title: header.title.clone(),
+        env: header.env.clone(),
+    };
+    serde_json::to_writer(&mut *writer, &v2)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn write_v2_event(
+    writer: &mut impl std::io::Write,
+    time: Duration,
+    event: &Event,
+    utf8: &mut Utf8Buffer,
+) -> Result<()> {
+    let time = time.as_secs_f64();
+    match event {
+        Event::Output(data) => serde_json::to_writer(&mut *writer, &(time, "o", utf8.push(data)))?,
+        Event::Resize(rows, cols) => {
+            serde_json::to_writer(&mut *writer, &(time, "r", format!("{cols}x{rows}")))?
+        }
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn write_v2_exit(writer: &mut impl std::io::Write, time: Duration, code: i32) -> Result<()> {
+    serde_json::to_writer(&mut *writer, &(time.as_secs_f64(), "x", code.to_string()))?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Write asciicast v2 while recording, the time of each event is up to the
+/// recorder
+pub struct StreamWriter<W> {
+    writer: W,
+    utf8: Utf8Buffer,
+}
+impl<W: std::io::Write> StreamWriter<W> {
+    pub fn new(mut writer: W, header: &Header) -> Result<Self> {
+        write_v2_header(&mut writer, header)?;
+        Ok(Self {
+            writer,
+            utf8: Utf8Buffer::default(),
+        })
+    }
+    pub fn event(&mut self, time: Duration, event: &Event) -> Result<()> {
+        write_v2_event(&mut self.writer, time, event, &mut self.utf8)
+    }
+    pub fn exit(&mut self, time: Duration, code: i32) -> Result<()> {
+        write_v2_exit(&mut self.writer, time, code)
+    }
+}
+
+/// Asciicast stores text, so keep incomplete utf-8 sequences at the end of a
+/// frame and prepend them to the next one.
+#[derive(Default)]
+struct Utf8Buffer(Vec<u8>);
+impl Utf8Buffer {
+    fn push(&mut self, data: &[u8]) -> String {
+        self.0.extend_from_slice(data);
+        let valid = match std::str::from_utf8(&self.0) {
+            Ok(_) => self.0.len(),
+            // incomplete sequence at the end, wait for more bytes
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.0.len(),
+        };
+        let rest = self.0.split_off(valid);
+        let text = String::from_utf8_lossy(&self.0).into_owned();
+        self.0 = rest;
+        text
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let cast = "{\"version\": 2, \"width\": 80, \"height\": 24}
+[0.5, \"o\", \"hello\\r\\n\"]
+[0.6, \"i\", \"q\"]
+[1.0, \"r\", \"100x30\"]
+";
+    let rec = read(cast.as_bytes()).unwrap();
+    assert_eq!(rec.header.size, Some((24, 80)));
+    assert_eq!(rec.frames.len(), 2);
+    assert!(matches!(rec.frames[1].event, Event::Resize(30, 100)));
+
+    let mut v1 = Vec::new();
+    write_v1(&rec, &mut v1).unwrap();
+    let rec = read(&v1).unwrap();
+    assert_eq!(rec.frames[0].time, Duration::from_millis(500));
+    assert_eq!(rec.duration(), Duration::from_secs(1));
+
+    let mut utf8 = Utf8Buffer::default();
+    let smile = "😀".as_bytes();
+    assert_eq!(utf8.push(&smile[..2]), "");
+    assert_eq!(utf8.push(&smile[2..]), "😀");
+}
@@ -0,0 +1,134 @@
+//! gzip and zstd around any of the formats, `tty.rec.gz` or `demo.cast.zst`
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+impl Compression {
+    /// `.gz` is gzip, `.zst` is zstd
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Self::Gzip,
+            Some("zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+    /// The file name without the compression extension, which is enough to
+    /// tell the format
+    pub fn strip(path: &Path) -> &Path {
+        match Self::from_path(path) {
+            Self::None => path,
+            _ => path.file_stem().map_or(path, Path::new),
+        }
+    }
+}
+
+/// Decompressed by the magic, whatever the extension is. A stream which ends
+/// early, like after a crash, gives what could be decoded.
+pub fn read_file(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 4];
+    let len = file.read(&mut magic)?;
+    let magic = &magic[..len];
+    let file = io::Cursor::new(magic.to_vec()).chain(file);
+    let mut reader: Box<dyn Read> = if magic.starts_with(&GZIP_MAGIC) {
+        // appending makes more members
+        Box::new(flate2::read::MultiGzDecoder::new(file))
+    } else if magic.starts_with(&ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::new(file)?)
+    } else {
+        Box::new(file)
+    };
+    let mut bytes = Vec::new();
+    match reader.read_to_end(&mut bytes) {
+        Ok(_) => Ok(bytes),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof || !bytes.is_empty() => {
+            tracing::warn!("The compressed stream is cut short: {e}");
+            Ok(bytes)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub enum Encoder<W: Write> {
+    None(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+impl<W: Write> Encoder<W> {
+    pub fn new(writer: W, compression: Compression) -> io::Result<Self> {
+        Ok(match compression {
+            Compression::None => Self::None(writer),
+            Compression::Gzip => Self::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => Self::Zstd(zstd::Encoder::new(writer, 0)?),
+        })
+    }
+    pub fn get_ref(&self) -> &W {
+        match self {
+            Self::None(writer) => writer,
+            Self::Gzip(encoder) => encoder.get_ref(),
+            Self::Zstd(encoder) => encoder.get_ref(),
+        }
+    }
+    /// Write the trailer, the stream can still be flushed but not written to
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Self::None(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.try_finish(),
+            Self::Zstd(encoder) => encoder.do_finish(),
+        }
+    }
+}
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::None(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+    /// Everything written so far can be decoded afterwards
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::None(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let dir = crate::utils::test_dir::TestDir::new("compress");
+    for (name, compression) in [
+        ("vtyrec-test.rec", Compression::None),
+        ("vtyrec-test.rec.gz", Compression::Gzip),
+        ("vtyrec-test.rec.zst", Compression::Zstd),
+    ] {
+        let path = dir.join(name);
+        assert!(Compression::from_path(&path) == compression);
+        assert_eq!(Compression::strip(&path).extension().unwrap(), "rec");
+        let mut encoder = Encoder::new(File::create(&path).unwrap(), compression).unwrap();
+        encoder.write_all(b"hello ").unwrap();
+        encoder.flush().unwrap();
+        // not finished, like when vtyrec gets killed
+        assert_eq!(read_file(&path).unwrap(), b"hello ");
+        encoder.write_all(b"world").unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(read_file(&path).unwrap(), b"hello world");
+    }
+}
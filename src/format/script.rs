@@ -0,0 +1,176 @@
+//! util-linux `script`, a typescript plus a timing file
+use super::{Event, Frame, Header, Recording};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use std::time::Duration;
+
+const STARTED: &[u8] = b"Script started";
+const DONE: &[u8] = b"\nScript done";
+
+/// Both the classic (`delay length`) and the advanced (`O delay length`)
+/// timing formats are accepted, input entries are skipped.
+pub fn read(typescript: &[u8], timing: &str) -> Result<Recording> {
+    let mut header = Header::default();
+    let mut data = typescript;
+    if data.starts_with(STARTED) {
+        let end = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+        header = parse_started(&String::from_utf8_lossy(&data[..end]));
+        data = data.get(end + 1..).unwrap_or_default();
+    }
+
+    let mut frames = Vec::new();
+    let mut time = Duration::ZERO;
+    let mut size = (None, None);
+    for (idx, line) in timing.lines().enumerate() {
+        let invalid = || eyre!("invalid timing entry at line {}: `{line}`", idx + 1);
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let (kind, delay, rest) = match fields.as_slice() {
+            [] => continue,
+            [delay, len] => ("O", delay, std::slice::from_ref(len)),
+            [kind, delay, rest @ ..] if kind.len() == 1 => (*kind, delay, rest),
+            _ => return Err(invalid()),
+        };
+        time += Duration::from_secs_f64(delay.parse::<f64>().map_err(|_| invalid())?.max(0.0));
+        let event = match (kind, rest) {
+            ("O", [len]) => {
+                let len = len.parse().map_err(|_| invalid())?;
+                if len > data.len() {
+                    bail!("timing file goes past the end of the typescript");
+                }
+                let (chunk, rest) = data.split_at(len);
+                data = rest;
+                Event::Output(chunk.to_vec())
+            }
+            ("S", ["SIGWINCH", size @ ..]) => {
+                let get = |key| size.iter().find_map(|s| s.strip_prefix(key)?.parse().ok());
+                match (get("ROWS="), get("COLS=")) {
+                    (Some(rows), Some(cols)) => Event::Resize(rows, cols),
+                    _ => continue,
+                }
+            }
+            // the advanced format stores the header here
+            ("H", [key, value @ ..]) => {
+                let value = value.join(" ");
+                match *key {
+                    "COLUMNS" => size.1 = value.parse().ok(),
+                    "LINES" => size.0 = value.parse().ok(),
+                    "TERM" => {
+                        header.env.insert(key.to_string(), value);
+                    }
+                    "COMMAND" => header.command = Some(value),
+                    _ => (),
+                }
+                continue;
+            }
+            // input and other signals
+            _ => continue,
+        };
+        frames.push(Frame { time, event });
+    }
+    if let (Some(rows), Some(cols)) = size {
+        header.size = Some((rows, cols));
+    }
+    // `Script done on ... [COMMAND_EXIT_CODE="0"]` is what's left
+    header.exit_status = String::from_utf8_lossy(data)
+        .split_once("COMMAND_EXIT_CODE=\"")
+        .and_then(|(_, rest)| rest.split('"').next()?.parse().ok());
+    Ok(Recording { header, frames })
+}
+
+/// `Script started on 2024-09-01 10:00:00+08:00 [TERM="xterm" COLUMNS="80" LINES="24"]`
+fn parse_started(line: &str) -> Header {
+    let mut header = Header::default();
+    let Some(fields) = line
+        .split_once('[')
+        .and_then(|(_, rest)| rest.rsplit_once(']'))
+        .map(|(fields, _)| fields)
+    else {
+        return header;
+    };
+    let mut size = (None, None);
+    for field in fields.split("\" ") {
+        let Some((key, value)) = field.split_once("=\"") else {
+            continue;
+        };
+        let value = value.trim_end_matches('"');
+        match key {
+            "COMMAND" => header.command = Some(value.to_string()),
+            "COLUMNS" => size.1 = value.parse().ok(),
+            "LINES" => size.0 = value.parse().ok(),
+            "TERM" => {
+                header.env.insert(key.to_string(), value.to_string());
+            }
+            _ => (),
+        }
+    }
+    if let (Some(rows), Some(cols)) = size {
+        header.size = Some((rows, cols));
+    }
+    header
+}
+
+/// Writes in the classic timing format, which `scriptreplay` reads by default.
+pub fn write(
+    rec: &Recording,
+    typescript: &mut impl std::io::Write,
+    timing: &mut impl std::io::Write,
+) -> Result<()> {
+    write!(typescript, "Script started by vtyrec [")?;
+    if let Some(command) = &rec.header.command {
+        write!(typescript, "COMMAND=\"{command}\" ")?;
+    }
+    if let Some(term) = rec.header.env.get("TERM") {
+        write!(typescript, "TERM=\"{term}\" ")?;
+    }
+    if let Some((rows, cols)) = rec.header.size {
+        write!(typescript, "COLUMNS=\"{cols}\" LINES=\"{rows}\"")?;
+    }
+    writeln!(typescript, "]")?;
+
+    let mut prev = Duration::ZERO;
+    for frame in &rec.frames {
+        let data = frame.event.to_bytes();
+        let delay = frame.time.saturating_sub(prev).as_secs_f64();
+        prev = frame.time;
+        typescript.write_all(&data)?;
+        writeln!(timing, "{delay:.6} {}", data.len())?;
+    }
+    typescript.write_all(DONE)?;
+    if let Some(code) = rec.header.exit_status {
+        write!(typescript, " [COMMAND_EXIT_CODE=\"{code}\"]")?;
+    }
+    writeln!(typescript)?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let typescript = b"Script started on 2024-09-01 10:00:00+08:00 [TERM=\"xterm-256color\" COLUMNS=\"100\" LINES=\"30\"]
+$ ls\r\nCargo.toml\r\n
+Script done on 2024-09-01 10:00:01+08:00 [COMMAND_EXIT_CODE=\"0\"]
+";
+    let timing = "H 0.000000 START_TIME 2024-09-01 10:00:00
+O 0.5 6
+I 0.1 3
+S 0.0 SIGWINCH ROWS=30 COLS=90
+O 0.2 12
+";
+    let rec = read(typescript, timing).unwrap();
+    assert_eq!(rec.header.size, Some((30, 100)));
+    assert_eq!(rec.header.env["TERM"], "xterm-256color");
+    assert_eq!(rec.header.exit_status, Some(0));
+    assert_eq!(rec.frames.len(), 3);
+    assert!(matches!(rec.frames[1].event, Event::Resize(30, 90)));
+    assert_eq!(rec.frames[2].time, Duration::from_millis(800));
+    assert_eq!(rec.frames[2].event.to_bytes(), b"Cargo.toml\r\n");
+
+    let (mut ts, mut tm) = (Vec::new(), Vec::new());
+    write(&rec, &mut ts, &mut tm).unwrap();
+    let again = read(&ts, std::str::from_utf8(&tm).unwrap()).unwrap();
+    assert_eq!(again.header.size, rec.header.size);
+    assert_eq!(again.header.exit_status, Some(0));
+    assert_eq!(again.frames.len(), 3);
+}
@@ -0,0 +1,38 @@
+use super::{Event, Frame, Header, Recording};
+use color_eyre::Result;
+
+/// Frame time is absolute in classic ttyrec while relative in vtyrec, so
+/// always rebase it to the first frame.
+///
+/// A truncated trailing frame is silently dropped.
+pub fn read(bytes: &[u8]) -> Recording {
+    let mut parser = ::ttyrec::Parser::new();
+    parser.add_bytes(bytes);
+    let mut frames = Vec::new();
+    let mut start = None;
+    while let Some(frame) = parser.next_frame() {
+        let start = *start.get_or_insert(frame.time);
+        frames.push(Frame {
+            time: frame.time.saturating_sub(start),
+            event: Event::Output(frame.data),
+        });
+    }
+    Recording {
+        header: Header::default(),
+        frames,
+    }
+}
+
+/// Resize events are written as `CSI 8 ; rows ; cols t`, since ttyrec has no
+/// way to store them.
+pub fn write(rec: &Recording, writer: &mut impl std::io::Write) -> Result<()> {
+    for frame in &rec.frames {
+        let bytes: Vec<u8> = ::ttyrec::Frame {
+            time: frame.time,
+            data: frame.event.to_bytes(),
+        }
+        .try_into()?;
+        writer.write_all(&bytes)?;
+    }
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use clap::Parser;
-use color_eyre::Result;
+use color_eyre::{eyre::eyre, Result};
 use crossterm::event;
+use format::{Format, Header};
 use portable_pty::CommandBuilder;
 use std::{
     io::{BufWriter, Write},
@@ -8,12 +9,15 @@ use std::{
 };
 use tui_term::vt100;
 use utils::{
-    child::{spawn_pty_child, CHILD_HAD_EXIT, CHILD_SHOULD_EXIT},
+    child::{spawn_pty_child, PtyChild},
     key2bytes::U8Code,
     recorder::{VtyParser, VtyrecWriter},
+    status::StatusBar,
 };
 
+mod format;
 mod parser;
+mod subcommand;
 mod utils;
 
 const DEFAULT_FILE_NAME: &str = "tty.rec";
@@ -21,15 +25,23 @@ const DEFAULT_SHELL: &str = "sh";
 
 #[derive(Parser)]
 #[command(version=env!("CARGO_PKG_VERSION"), about, long_about)]
+#[command(args_conflicts_with_subcommands = true)]
 /// Vtyrec is a tty recorder.  It aims to be a rust impl of ttyrec, with extended functions,
 /// such as vhs-like script.
+///
+/// Vtyrec exits with the child's exit code, `128 + signal` if it got killed. Asciicast and
+/// script recordings store it too, ttyrec has no place for it.
 struct Cli {
     /// Invoke <command> when ttyrec starts.
     ///
     /// If the variable <SHELL> exists, the shell forked by vtyrec will be that shell.
     /// Otherwise, 'sh' is assumed
-    #[arg(short = 'e')]
+    #[arg(short = 'e', conflicts_with_all = ["exec", "argv"])]
     command: Option<std::ffi::OsString>,
+    /// Run <exec> with `<SHELL> -c` instead of an interactive shell, the
+    /// recording ends when it exits.
+    #[arg(short = 'c', conflicts_with = "argv")]
+    exec: Option<std::ffi::OsString>,
     /// Append the output to <FILE>, rather than overwriting it.
     #[arg(short = 'a')]
     append: bool,
@@ -39,32 +51,108 @@ struct Cli {
     #[arg(default_value = DEFAULT_FILE_NAME)]
     file: std::ffi::OsString,
     /// support vhs-like script
-    #[arg(short = 's')]
+    ///
+    /// The shell is hung up once the tape is over, which counts as exit code 0.
+    /// Only a shell exiting by itself before that gives its own code.
+    #[arg(short = 's', conflicts_with_all = ["command", "exec", "argv"])]
     script: Option<std::ffi::OsString>,
+    /// Set `${NAME}` in the script, over its `Let NAME` and the environment
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_env, requires = "script")]
+    vars: Vec<(String, String)>,
+    /// The prefix of vtyrec's hotkeys, press it twice to send it to the child.
+    ///
+    /// <prefix> then `p` pauses or resumes the recording. The screen is
+    /// recorded as it is on resume, so clear anything private before that.
+    ///
+    /// <prefix> then `[` browses the scrollback, up to a screen up, with the
+    /// arrows, `j`/`k`, PageUp/PageDown and `g`/`G`, until `q` or Esc.
+    #[arg(long, default_value = utils::hotkey::DEFAULT_PREFIX)]
+    prefix: utils::hotkey::Prefix,
+    /// Rows of scrollback to keep
+    #[arg(long, default_value_t = utils::scroll::DEFAULT_SCROLLBACK)]
+    scrollback: usize,
+    /// Mask secrets like tokens and keys in the recording, the screen still
+    /// shows them.
+    ///
+    /// The word being written shows up in the recording once it's done, so
+    /// that a secret is matched as a whole.
+    #[arg(long)]
+    redact: bool,
+    /// More patterns to mask, one regex per line, implies `--redact`
+    #[arg(long, value_name = "FILE")]
+    redact_rules: Option<std::path::PathBuf>,
+    /// Compress the recording, taken from the extension of <FILE> like
+    /// `tty.rec.gz` or `demo.cast.zst` if not given
+    #[arg(long, value_enum)]
+    compress: Option<format::Compression>,
+    /// Show a status line below the terminal. It's not recorded, and takes
+    /// a row from the child unless the size is set by a tape.
+    #[arg(long)]
+    status: bool,
+    /// Set an environment variable for the child, can be repeated
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env)]
+    env: Vec<(String, String)>,
+    /// The working directory of the child, defaults to the current one
+    #[arg(long, value_name = "DIR")]
+    cwd: Option<std::path::PathBuf>,
+    /// The child's `TERM`
+    #[arg(long)]
+    term: Option<String>,
+    /// The child's `COLORTERM`, like `truecolor`
+    #[arg(long)]
+    colorterm: Option<String>,
+    /// Run this program with its arguments directly, like `-c` but without
+    /// a shell
+    #[arg(last = true)]
+    argv: Vec<std::ffi::OsString>,
+    #[command(subcommand)]
+    subcommand: Option<subcommand::SubCommand>,
 }
 
 fn main() -> Result<()> {
-    use portable_pty::{NativePtySystem, PtyPair, PtySize, PtySystem};
-    color_eyre::config::HookBuilder::new().install()?;
+    utils::cleanup::install()?;
     let mut cli = Cli::parse();
+    if let Some(subcommand) = cli.subcommand {
+        return subcommand.run();
+    }
 
-    let (mut cmd, size, event_list) = if let Some(script) = cli.script {
-        let script_host = parser::Parser::from_reader(std::fs::File::open(script)?)?;
+    let (mut cmd, size, event_list) = if let Some(script) = cli.script.take() {
+        let script_host = parser::Parser::from_file(script, parser::Vars::new(cli.vars.clone()))
+            .map_err(|e| {
+                match e.downcast::<parser::Diagnostics>() {
+                    // already readable, without the report around it
+                    Ok(diagnostics) => {
+                        eprintln!("{diagnostics}");
+                        std::process::exit(1)
+                    }
+                    Err(e) => e,
+                }
+            })?;
         cli.append = false;
-        cli.file = script_host.env.file_name.into();
+        cli.file = script_host.env.file_name.clone().into();
         cli.command = None;
+        let mut cmd = CommandBuilder::new(&script_host.env.shell);
+        script_host.env.apply(&mut cmd);
+        let mut typing = script_host.env.typing();
         (
-            CommandBuilder::new(script_host.env.shell),
+            cmd,
             Some(script_host.env.size)
                 .map(|(height, width)| ratatui::layout::Size { height, width }),
             Some(
                 script_host
-                    .commands
+                    .expand()
                     .into_iter()
-                    .flat_map(|c| c.into_key(script_host.env.typingspeed))
+                    .flat_map(|c| c.into_steps(&mut typing))
                     .collect(),
             ),
         )
+    } else if !cli.argv.is_empty() {
+        (CommandBuilder::from_argv(cli.argv.clone()), None, None)
+    } else if let Some(exec) = &cli.exec {
+        let mut cmd =
+            CommandBuilder::new(std::env::var_os("SHELL").unwrap_or(DEFAULT_SHELL.into()));
+        cmd.args([std::ffi::OsStr::new("-c"), exec]);
+        (cmd, None, None)
     } else {
         (
             CommandBuilder::new(std::env::var_os("SHELL").unwrap_or(DEFAULT_SHELL.into())),
@@ -72,15 +160,99 @@ fn main() -> Result<()> {
             None,
         )
     };
-    cmd.cwd(std::env::current_dir()?);
+    for (key, value) in &cli.env {
+        cmd.env(key, value);
+    }
+    if let Some(term) = &cli.term {
+        cmd.env("TERM", term);
+    }
+    if let Some(colorterm) = &cli.colorterm {
+        cmd.env("COLORTERM", colorterm);
+    }
+    match cli.cwd.take() {
+        Some(cwd) => cmd.cwd(cwd),
+        None if cmd.get_cwd().is_none() => cmd.cwd(std::env::current_dir()?),
+        None => (),
+    }
+    let rec_format = Format::from_path(&cli.file);
+    if cli.append && rec_format != Format::Ttyrec {
+        color_eyre::eyre::bail!("Only ttyrec can be appended to");
+    }
 
-    let mut terminal = ratatui::try_init()?;
-    let size = size.unwrap_or(terminal.size()?);
+    let redactor = if cli.redact || cli.redact_rules.is_some() {
+        let mut redactor = utils::redact::Redactor::builtin();
+        if let Some(rules) = &cli.redact_rules {
+            redactor.add_rules(rules)?;
+        }
+        Some(redactor)
+    } else {
+        None
+    };
+
+    let mut terminal = utils::cleanup::init_terminal()?;
+    let result = record(
+        &mut terminal,
+        cli,
+        cmd,
+        size,
+        event_list,
+        rec_format,
+        redactor,
+    );
+    drop(terminal);
+    let exit_code = match result {
+        Ok(exit_code) => {
+            utils::cleanup::restore_terminal()?;
+            exit_code
+        }
+        Err(e) => {
+            utils::cleanup::run();
+            return Err(e);
+        }
+    };
+    if exit_code != 0 {
+        eprintln!("vtyrec: child exited with {exit_code}");
+        std::process::exit(exit_code);
+    }
+    Ok(())
+}
+
+/// Everything between setting up the terminal and restoring it, so that a
+/// failure still gets to restore it and finish the recording
+fn record(
+    terminal: &mut ratatui::DefaultTerminal,
+    cli: Cli,
+    cmd: CommandBuilder,
+    size: Option<ratatui::layout::Size>,
+    event_list: Option<Vec<(parser::Step, u64)>>,
+    rec_format: Format,
+    redactor: Option<utils::redact::Redactor>,
+) -> Result<i32> {
+    use portable_pty::{NativePtySystem, PtyPair, PtySize, PtySystem};
+    let status_rows = u16::from(cli.status);
+    let size = match size {
+        Some(size) => size,
+        None => {
+            let mut size = terminal.size()?;
+            size.height = size.height.saturating_sub(status_rows).max(1);
+            size
+        }
+    };
+    let command = if cli.argv.is_empty() {
+        cli.command.clone().or(cli.exec)
+    } else {
+        Some(shell_words(&cli.argv))
+    };
+    let mut header = recording_header(&cmd, size, command.as_deref());
+    if !cli.argv.is_empty() {
+        // argv[0] is not a shell this time
+        header.env.remove("SHELL");
+    }
 
     let parser = VtyParser::new(std::sync::RwLock::new(vt100::Parser::new(
         size.height,
         size.width,
-        0,
+        cli.scrollback,
     )));
     let PtyPair { slave, master } = NativePtySystem::default()
         .openpty(PtySize {
@@ -89,15 +261,16 @@ fn main() -> Result<()> {
             pixel_width: 0,
             pixel_height: 0,
         })
-        .unwrap();
-    spawn_pty_child(
-        cmd,
-        master.try_clone_reader().unwrap(),
-        slave,
-        parser.clone(),
-    );
+        .map_err(|e| eyre!("Failed to open a pty: {e:#}"))?;
+    let reader = master
+        .try_clone_reader()
+        .map_err(|e| eyre!("Failed to read from the pty: {e:#}"))?;
+    let mut child = spawn_pty_child(cmd, reader, slave, parser.clone())?;
 
-    let mut writer = BufWriter::new(master.take_writer().unwrap());
+    let writer = master
+        .take_writer()
+        .map_err(|e| eyre!("Failed to write to the pty: {e:#}"))?;
+    let mut writer = BufWriter::new(writer);
     if let Some(pgm) = cli.command {
         // waiting for <shell> to be ready
         // this affect ui only, the record file is fine
@@ -106,46 +279,171 @@ fn main() -> Result<()> {
         writer.write_all(&[utils::key2bytes::ascii::ENTER])?;
         writer.flush()?;
     }
-    let ttyrec_writer =
-        VtyrecWriter::open(cli.file, cli.append).map(|writer| VtyrecWriter::new(writer, parser))?;
+    let status_bar = cli.status.then(|| utils::status::StatusBar::new(&cli.file));
+    if let Some(redactor) = &redactor {
+        redactor.redact_header(&mut header);
+    }
+    let mut ttyrec_writer = VtyrecWriter::new(
+        VtyrecWriter::open(
+            &cli.file,
+            cli.append,
+            cli.compress
+                .unwrap_or_else(|| format::Compression::from_path(&cli.file)),
+        )?,
+        rec_format,
+        &header,
+        parser,
+    )?;
+    if let Some(redactor) = redactor {
+        ttyrec_writer = ttyrec_writer.redacted(redactor);
+    }
 
-    if let Some(events) = event_list {
-        run_script(&mut terminal, writer, events, ttyrec_writer)?
+    let exit_code = if let Some(events) = event_list {
+        run_script(
+            terminal,
+            writer,
+            events,
+            &mut ttyrec_writer,
+            child,
+            status_bar.as_ref(),
+        )?
     } else {
-        run_interactive(&mut terminal, writer, ttyrec_writer)?
+        run_interactive(
+            terminal,
+            writer,
+            &mut ttyrec_writer,
+            &mut child,
+            &cli.prefix,
+            status_bar.as_ref(),
+        )?
     };
+    ttyrec_writer.finish(exit_code)?;
+    Ok(exit_code)
+}
 
-    // restore terminal
-    drop(terminal);
-    ratatui::try_restore()?;
-    Ok(())
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("`{s}` should be like KEY=VALUE"))
+}
+
+/// Only for showing it, quotes the words which need it
+fn shell_words(argv: &[std::ffi::OsString]) -> std::ffi::OsString {
+    let words: Vec<_> = argv
+        .iter()
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+            {
+                arg.into_owned()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect();
+    words.join(" ").into()
+}
+
+fn recording_header(
+    cmd: &CommandBuilder,
+    size: ratatui::layout::Size,
+    command: Option<&std::ffi::OsStr>,
+) -> Header {
+    let env = [
+        ("SHELL", cmd.get_argv().first().map(|s| s.as_os_str())),
+        ("TERM", cmd.get_env("TERM")),
+        ("COLORTERM", cmd.get_env("COLORTERM")),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key.to_string(), value?.to_string_lossy().into_owned())))
+    .collect();
+    Header {
+        size: Some((size.height, size.width)),
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs()),
+        command: command.map(|c| c.to_string_lossy().into_owned()),
+        env,
+        ..Default::default()
+    }
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn run_interactive(
     terminal: &mut ratatui::DefaultTerminal,
     mut pty_writer: BufWriter<Box<dyn Write + Send>>,
-    mut rec_writer: VtyrecWriter,
-) -> Result<()> {
+    rec_writer: &mut VtyrecWriter,
+    child: &mut PtyChild,
+    prefix: &utils::hotkey::Prefix,
+    status_bar: Option<&StatusBar>,
+) -> Result<i32> {
     use event::{Event, EventStream, KeyEventKind};
     use tokio_stream::StreamExt;
     let mut evs = EventStream::new();
+    // the prefix was pressed, the next key is for us
+    let mut prefixed = false;
+    // rows up in the scrollback, keys are for us then too
+    let mut scroll: Option<usize> = None;
     let mut timeout = tokio::time::interval(Duration::from_millis(20));
     loop {
-        let now_screen = rec_writer.tick()?;
-        terminal.draw(|f| ui(f, now_screen))?;
-
-        if CHILD_HAD_EXIT.is_completed() {
-            return Ok(());
+        let status = status_bar.map(|bar| bar.line(rec_writer));
+        let scrolled = scroll.map(|rows| rec_writer.scrolled(rows));
+        rec_writer.tick()?;
+        {
+            let parser = rec_writer.as_ref().read().unwrap();
+            let screen = match &scrolled {
+                Some((screen, rows)) => {
+                    scroll = Some(*rows);
+                    screen
+                }
+                None => parser.screen(),
+            };
+            terminal.draw(|f| {
+                let area = ui(f, screen, status);
+                if let Some(rows) = scroll {
+                    utils::scroll::indicator(f, area, rows);
+                }
+            })?;
         }
 
         let ev = tokio::select! {
             e = evs.next() => e,
             _ = timeout.tick() => None,
+            exit_code = &mut child.exit => return Ok(exit_code?),
         };
 
         if let Some(ev) = ev {
             match ev? {
+                Event::Key(key) if key.kind == KeyEventKind::Press && prefixed => {
+                    prefixed = false;
+                    if prefix.matches(&key) {
+                        pty_writer.write_all(&[prefix.byte()])?;
+                        pty_writer.flush()?;
+                    } else if key.code == utils::hotkey::PAUSE {
+                        rec_writer.set_paused(!rec_writer.paused())?;
+                    } else if key.code == utils::hotkey::SCROLL {
+                        scroll = Some(0);
+                    }
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press && scroll.is_some() => {
+                    use event::KeyCode;
+                    let rows = rec_writer.as_ref().read().unwrap().screen().size().0;
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => scroll = None,
+                        code => {
+                            scroll =
+                                utils::scroll::on_key(code, scroll.unwrap_or(0), rows).or(scroll)
+                        }
+                    }
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press && prefix.matches(&key) => {
+                    prefixed = true;
+                }
                 Event::Key(key) if key.kind == KeyEventKind::Press => {
                     use utils::key2bytes::ToBytes;
                     let byte = key.into_byte_code();
@@ -162,6 +460,7 @@ async fn run_interactive(
                 Event::Mouse(_) => tracing::trace!("mouse event get, ignored"),
                 Event::Paste(_) => unimplemented!("should be handled by outside"),
                 Event::Resize(cols, rows) => {
+                    let rows = rows.saturating_sub(status_bar.map_or(0, |_| 1)).max(1);
                     rec_writer.as_ref().write().unwrap().set_size(rows, cols);
                 }
             }
@@ -169,31 +468,79 @@ async fn run_interactive(
     }
 }
 
+/// The child's exit code if it exits before the tape is over, 0 otherwise
 fn run_script(
     terminal: &mut ratatui::DefaultTerminal,
     mut pty_writer: BufWriter<Box<dyn Write + Send>>,
-    events: Vec<(U8Code, u64)>,
-    mut rec_writer: VtyrecWriter,
-) -> Result<()> {
-    for (code, delay) in events {
-        let now_screen = rec_writer.tick()?;
-        terminal.draw(|f| ui(f, now_screen))?;
+    events: Vec<(parser::Step, u64)>,
+    rec_writer: &mut VtyrecWriter,
+    mut child: PtyChild,
+    status_bar: Option<&StatusBar>,
+) -> Result<i32> {
+    for (step, delay) in events {
+        let status = status_bar.map(|bar| bar.line(rec_writer));
+        rec_writer.tick()?;
+        let parser = rec_writer.as_ref().read().unwrap();
+        terminal.draw(|f| {
+            ui(f, parser.screen(), status);
+        })?;
+        drop(parser);
         std::thread::sleep(Duration::from_millis(delay));
-        match code {
-            U8Code::Ascii(byte) => pty_writer.write_all(&[byte])?,
-            U8Code::TriU8(bytes) => pty_writer.write_all(&bytes)?,
-            U8Code::Auto(vec) => pty_writer.write_all(&vec)?,
+        let code = match step {
+            parser::Step::Key(code) => code,
+            parser::Step::Hide => {
+                rec_writer.set_paused(true)?;
+                continue;
+            }
+            parser::Step::Show => {
+                rec_writer.set_paused(false)?;
+                continue;
+            }
+        };
+        // the tape may have ended the child already, like with `exit`
+        if let Ok(exit_code) = child.exit.try_recv() {
+            return Ok(exit_code);
+        }
+        let written = match code {
+            U8Code::Ascii(byte) => pty_writer.write_all(&[byte]),
+            U8Code::TriU8(bytes) => pty_writer.write_all(&bytes),
+            U8Code::Auto(vec) => pty_writer.write_all(&vec),
+        }
+        .and_then(|()| pty_writer.flush());
+        if let Err(e) = written {
+            // most likely the child just went away
+            return child.exit.blocking_recv().map_err(|_| e.into());
         }
-        pty_writer.flush()?;
     }
-    CHILD_SHOULD_EXIT.call_once(|| ());
-    // waiting for child
-    while !CHILD_HAD_EXIT.is_completed() {}
-    Ok(())
+    if let Ok(exit_code) = child.exit.try_recv() {
+        return Ok(exit_code);
+    }
+    // the tape is over, so is the shell, which is not a failure
+    if let Err(e) = child.kill() {
+        // it was on its way out already
+        return child.exit.blocking_recv().map_err(|_| e.into());
+    }
+    // its code would only tell it got hung up
+    child.exit.blocking_recv()?;
+    Ok(0)
 }
 
-fn ui(f: &mut ratatui::Frame, screen: &vt100::Screen) {
+/// Returns where the terminal is
+fn ui(
+    f: &mut ratatui::Frame,
+    screen: &vt100::Screen,
+    status: Option<ratatui::text::Line>,
+) -> ratatui::layout::Rect {
+    use ratatui::layout::{Constraint, Layout};
     use tui_term::widget::PseudoTerminal;
     let pseudo_term = PseudoTerminal::new(screen);
-    f.render_widget(pseudo_term, f.area());
+    let Some(status) = status else {
+        f.render_widget(pseudo_term, f.area());
+        return f.area();
+    };
+    let [area, line] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(f.area());
+    f.render_widget(pseudo_term, area);
+    f.render_widget(status, line);
+    area
 }
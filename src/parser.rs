@@ -1,28 +1,504 @@
-use std::io::{BufRead, BufReader, Read};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
+mod ast;
 mod command;
 mod env;
 mod error;
+mod string;
+mod typing;
 mod utils;
+mod vars;
+
+pub use ast::Node;
+pub use command::{Commands, Step};
+pub use env::EnVar;
+pub use error::{Diagnostic, Diagnostics, Level, ParseError};
+pub use typing::Typing;
+pub use utils::parse_sleep;
+pub use vars::{uses_vars, Vars};
+
+/// A command of the tape or of a tape it sources, and where it's written
+pub struct Located {
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    pub source: String,
+    pub cmd: Commands,
+}
 
 pub struct Parser {
     pub env: env::Envs,
-    pub commands: Vec<command::Commands>,
+    pub nodes: Vec<Node>,
+    /// Every command as written, with the `Source`d ones in place of the
+    /// `Source`, for lints
+    pub commands: Vec<Located>,
+    macros: HashMap<String, ast::Body>,
+    vars: Vars,
 }
 impl Parser {
-    pub fn from_reader<R: Read>(rdr: R) -> color_eyre::Result<Self> {
-        let rdr = BufReader::new(rdr);
-        let mut vec = Vec::with_capacity(1024);
-        let mut env = env::Envs::default();
-        for line in rdr.lines() {
-            let cmd = line?.parse()?;
+    pub fn from_file(path: impl AsRef<Path>, vars: Vars) -> color_eyre::Result<Self> {
+        let (parser, errors) = Self::from_file_with_errors(path, vars)?;
+        if !errors.is_empty() {
+            return Err(error::Diagnostics(errors).into());
+        }
+        Ok(parser)
+    }
+    /// Like [`Self::from_file`], but what could be parsed is kept along with
+    /// the errors
+    pub fn from_file_with_errors(
+        path: impl AsRef<Path>,
+        vars: Vars,
+    ) -> color_eyre::Result<(Self, Vec<Diagnostic>)> {
+        use color_eyre::eyre::WrapErr;
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read `{}`", path.display()))?;
+        let stack = vec![path.canonicalize()?];
+        Ok(Self::parse_with_errors(
+            &text,
+            &path.display().to_string(),
+            path.parent().unwrap_or(Path::new("")),
+            stack,
+            vars,
+        ))
+    }
+    #[cfg(test)]
+    fn parse(
+        text: &str,
+        name: &str,
+        dir: &Path,
+        stack: Vec<PathBuf>,
+        vars: Vars,
+    ) -> color_eyre::Result<Self> {
+        let (parser, errors) = Self::parse_with_errors(text, name, dir, stack, vars);
+        if !errors.is_empty() {
+            return Err(error::Diagnostics(errors).into());
+        }
+        Ok(parser)
+    }
+    /// `name` is only for the diagnostics, which are all collected before
+    /// giving up
+    fn parse_with_errors(
+        text: &str,
+        name: &str,
+        dir: &Path,
+        mut stack: Vec<PathBuf>,
+        vars: Vars,
+    ) -> (Self, Vec<Diagnostic>) {
+        let mut parser = Self {
+            env: env::Envs::default(),
+            nodes: Vec::new(),
+            commands: Vec::new(),
+            macros: HashMap::new(),
+            vars,
+        };
+        let mut body = ast::Body {
+            nodes: Vec::with_capacity(1024),
+            len: 0,
+        };
+        let mut errors = Vec::new();
+        parser.include(text, name, dir, &mut stack, &mut body, &mut errors);
+        parser.nodes = body.nodes;
+        (parser, errors)
+    }
+    /// Every `Source` is inlined, with its path relative to `dir`, where the
+    /// tape sourcing it is. `stack` is the tapes being sourced right now, to
+    /// catch the ones which end up sourcing themselves. A `Repeat` or a
+    /// `Define` has to end in the tape it starts in. Expanding it all can't go
+    /// past [`ast::MAX_LEN`] steps.
+    fn include(
+        &mut self,
+        text: &str,
+        name: &str,
+        dir: &Path,
+        stack: &mut Vec<PathBuf>,
+        out: &mut ast::Body,
+        errors: &mut Vec<Diagnostic>,
+    ) {
+        let fail = |idx: usize, line: &str, message: String| {
+            Diagnostic::new(name, idx + 1, line, ParseError(message.into()))
+        };
+        let too_long = |idx: usize, line: &str| {
+            fail(
+                idx,
+                line,
+                format!("the tape expands to more than {} steps", ast::MAX_LEN),
+            )
+            .help("lower the count of a `Repeat`")
+        };
+        // (`Repeat` or `Define`, its body so far, its line index, its line)
+        let mut open: Vec<(Commands, ast::Body, usize, &str)> = Vec::new();
+        let mut lines = Lines::new(text);
+        while let Some((idx, line, cmd)) = lines.next(Some(&self.vars)) {
+            let cmd = match cmd {
+                Ok(cmd) => cmd,
+                Err(e) => {
+                    errors.push(error::Diagnostic::new(name, idx + 1, line, e));
+                    continue;
+                }
+            };
+            if !matches!(
+                cmd,
+                Commands::Source(_) | Commands::Let(..) | Commands::Null
+            ) {
+                self.commands.push(Located {
+                    file: name.to_string(),
+                    line: idx + 1,
+                    source: line.to_string(),
+                    cmd: cmd.clone(),
+                });
+            }
+            let body = match open.last_mut() {
+                Some((_, body, ..)) => body,
+                None => &mut *out,
+            };
             match cmd {
-                command::Commands::Output(name) => env.file_name = name,
-                command::Commands::Set(en_var) => env.set(en_var),
-                command::Commands::Null => (),
-                _ => vec.push(cmd),
+                Commands::Source(path) => {
+                    let path = dir.join(path);
+                    let text = match std::fs::read_to_string(&path) {
+                        Ok(text) => text,
+                        Err(e) => {
+                            errors.push(fail(
+                                idx,
+                                line,
+                                format!("can't read `{}`: {e}", path.display()),
+                            ));
+                            continue;
+                        }
+                    };
+                    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+                    if stack.contains(&canonical) {
+                        errors.push(fail(
+                            idx,
+                            line,
+                            format!("`{}` ends up sourcing itself", path.display()),
+                        ));
+                        continue;
+                    }
+                    stack.push(canonical);
+                    let dir = path.parent().unwrap_or(Path::new(""));
+                    self.include(&text, &path.display().to_string(), dir, stack, body, errors);
+                    stack.pop();
+                }
+                Commands::Repeat(_) | Commands::Define(_) => {
+                    open.push((cmd, ast::Body::default(), idx, line))
+                }
+                Commands::End => match open.pop() {
+                    Some((Commands::Repeat(times), inner, start, start_line)) => {
+                        let body = match open.last_mut() {
+                            Some((_, body, ..)) => body,
+                            None => &mut *out,
+                        };
+                        let len = times.saturating_mul(inner.len.saturating_add(1));
+                        // past it already, which was reported inside
+                        let reported = inner.len > ast::MAX_LEN;
+                        if body.push(Node::Repeat(times, inner.nodes), len) && !reported {
+                            errors.push(too_long(start, start_line));
+                        }
+                    }
+                    Some((Commands::Define(macro_name), nodes, start, start_line)) => {
+                        use std::collections::hash_map::Entry;
+                        match self.macros.entry(macro_name) {
+                            Entry::Occupied(entry) => errors.push(fail(
+                                start,
+                                start_line,
+                                format!("`{}` is already defined", entry.key()),
+                            )),
+                            Entry::Vacant(entry) => {
+                                entry.insert(nodes);
+                            }
+                        }
+                    }
+                    Some(_) => unreachable!(),
+                    None => errors.push(fail(idx, line, "unexpected `}`, nothing to end".into())),
+                },
+                Commands::Call(macro_name) => match self.macros.get(&macro_name) {
+                    Some(called) => {
+                        let len = called.len;
+                        if body.push(Node::Call(macro_name), len) && len <= ast::MAX_LEN {
+                            errors.push(too_long(idx, line));
+                        }
+                    }
+                    None => errors.push(fail(
+                        idx,
+                        line,
+                        format!("`{macro_name}` is not defined before this"),
+                    )),
+                },
+                Commands::Output(name) => self.env.file_name = name,
+                Commands::Set(EnVar::FontSize(_)) => errors.push(
+                    fail(idx, line, "`FontSize` is not supported".into())
+                        .help("remove it, vtyrec records the terminal and not its font"),
+                ),
+                Commands::Set(en_var) => self.env.set(en_var),
+                Commands::Env(key, value) => self.env.vars.push((key, value)),
+                Commands::Let(name, value) => self.vars.set(name, value),
+                Commands::Null => (),
+                cmd => {
+                    if body.push(Node::Command(cmd), 1) {
+                        errors.push(too_long(idx, line));
+                    }
+                }
             }
         }
-        Ok(Self { commands: vec, env })
+        for (cmd, _, idx, line) in open {
+            let what = cmd.to_string();
+            errors.push(fail(
+                idx,
+                line,
+                format!(
+                    "`{}` is never ended, missing `}}`",
+                    what.trim_end_matches(" {")
+                ),
+            ));
+        }
+    }
+    /// The tape as plain commands, with every `Repeat` and `Call` expanded
+    pub fn expand(&self) -> Vec<Commands> {
+        let mut commands = Vec::with_capacity(1024);
+        ast::expand(&self.nodes, &self.macros, &mut commands);
+        commands
     }
 }
+
+/// The lines of a tape, parsed one at a time so that each is interpolated
+/// with the `Let`s before it
+pub struct Lines<'a>(std::iter::Enumerate<std::str::Lines<'a>>);
+impl<'a> Lines<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self(text.lines().enumerate())
+    }
+    /// (index, source, command), where the lines of a block end up in
+    /// [`Commands::Block`] instead of on their own. A block line which can't
+    /// be interpolated is the one given, with its error.
+    pub fn next(
+        &mut self,
+        vars: Option<&Vars>,
+    ) -> Option<(usize, &'a str, Result<Commands, ParseError>)> {
+        let (idx, source) = self.0.next()?;
+        let mut cmd = match vars {
+            Some(vars) => vars.parse(source),
+            None => source.parse(),
+        };
+        if let Ok(Commands::Block {
+            lines: body,
+            delimiter,
+            ..
+        }) = &mut cmd
+        {
+            let mut closed = false;
+            let mut failed = None;
+            for (line_idx, line) in self.0.by_ref() {
+                if line.trim() == delimiter {
+                    closed = true;
+                    break;
+                }
+                match vars.map(|vars| vars.interpolate(line)) {
+                    Some(Ok(line)) => body.push(line.into_owned()),
+                    Some(Err(e)) => {
+                        failed.get_or_insert((line_idx, line, e));
+                    }
+                    None => body.push(line.to_string()),
+                }
+            }
+            if !closed {
+                let message = format!("unterminated block, missing `{delimiter}`");
+                cmd = Err(ParseError(message.into()));
+            } else if let Some((idx, line, e)) = failed {
+                return Some((idx, line, Err(e)));
+            }
+        }
+        Some((idx, source, cmd))
+    }
+}
+
+/// Every line as is, without interpolating anything
+pub fn parse_lines(text: &str) -> Vec<(usize, &str, Result<Commands, ParseError>)> {
+    let mut lines = Lines::new(text);
+    std::iter::from_fn(|| lines.next(None)).collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let tape = "Output demo.cast\nTypo \"hello\"\nSet TypingSped 10ms\nSleep soon\nEnter\n";
+    let err = Parser::parse(
+        tape,
+        "demo.tape",
+        Path::new(""),
+        Vec::new(),
+        Vars::default(),
+    )
+    .err()
+    .unwrap();
+    let diagnostics = err.downcast_ref::<error::Diagnostics>().unwrap();
+    let found: Vec<_> = diagnostics
+        .0
+        .iter()
+        .map(|d| (d.line, d.span.clone(), d.help.as_deref()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (2, 0..4, Some("did you mean `Type`?")),
+            (3, 4..14, Some("did you mean `TypingSpeed`?")),
+            (4, 6..10, None),
+        ]
+    );
+    let tape = "Type@10ms <<EOF\nab\n\n e\u{301}\nEOF\nPaste <<X\na\nb\nX\n";
+    let keys: Vec<_> = Parser::parse(
+        tape,
+        "block.tape",
+        Path::new(""),
+        Vec::new(),
+        Vars::default(),
+    )
+    .unwrap()
+    .expand()
+    .into_iter()
+    .flat_map(|c| c.into_key(&mut Typing::new(100, 0, 0)))
+    .map(|(key, delay)| match key {
+        crate::utils::key2bytes::U8Code::Auto(bytes) => (String::from_utf8(bytes).unwrap(), delay),
+        crate::utils::key2bytes::U8Code::Ascii(byte) => (char::from(byte).to_string(), delay),
+        crate::utils::key2bytes::U8Code::TriU8(_) => unreachable!(),
+    })
+    .collect();
+    let keys: Vec<_> = keys.iter().map(|(k, d)| (k.as_str(), *d)).collect();
+    assert_eq!(
+        keys,
+        [
+            ("a", 10),
+            ("b", 10),
+            ("\n", 10),
+            ("\n", 10),
+            (" ", 10),
+            ("e\u{301}", 10),
+            ("\x1b[200~a\rb\x1b[201~", 100),
+        ]
+    );
+    // blocks are interpolated too, down to the line of the error
+    let tape =
+        "Let who world\nPaste <<EOF\nhi ${who}\necho $${HOME}\nEOF\nType <<EOF\n\n${nope}\nEOF\n";
+    let parse = |tape| {
+        Parser::parse(
+            tape,
+            "vars.tape",
+            Path::new(""),
+            Vec::new(),
+            Vars::default(),
+        )
+    };
+    let err = parse(tape).err().unwrap();
+    let diagnostics = err.downcast_ref::<error::Diagnostics>().unwrap();
+    let found: Vec<_> = diagnostics.0.iter().map(|d| d.line).collect();
+    assert_eq!(found, [8]);
+    let commands = parse(tape.split_once("Type").unwrap().0).unwrap().expand();
+    assert!(matches!(
+        &commands[..],
+        [Commands::Block { lines, .. }] if lines == &["hi world", "echo ${HOME}"]
+    ));
+
+    let dir = crate::utils::test_dir::TestDir::new("source");
+    let main = dir.write("main.tape", "Source common/preamble.tape\nType \"ls\"\n");
+    dir.write(
+        "common/preamble.tape",
+        "Set Width 120\nSource ../prompt.tape\n",
+    );
+    dir.write("prompt.tape", "Type \"PS1='$ '\"\nEnter\n");
+    let parser = Parser::from_file(&main, Vars::default()).unwrap();
+    assert_eq!(parser.env.size.1, 120);
+    assert_eq!(parser.expand().len(), 3);
+
+    dir.write("prompt.tape", "Enter\nSource main.tape\nTypo\n");
+    let err = Parser::from_file(&main, Vars::default()).err().unwrap();
+    let diagnostics = err.downcast_ref::<error::Diagnostics>().unwrap();
+    let found: Vec<_> = diagnostics
+        .0
+        .iter()
+        .map(|d| (d.file.as_str(), d.line))
+        .collect();
+    let prompt = dir.join("common/../prompt.tape").display().to_string();
+    assert_eq!(found, [(prompt.as_str(), 2), (prompt.as_str(), 3)]);
+
+    let tape =
+        "Define item {\nDown 3\nEnter\n}\nRepeat 2 {\nCall item\nRepeat 2 {\nTab\n}\n}\nSleep 1s\n";
+    let commands: Vec<_> = Parser::parse(
+        tape,
+        "loop.tape",
+        Path::new(""),
+        Vec::new(),
+        Vars::default(),
+    )
+    .unwrap()
+    .expand()
+    .iter()
+    .map(ToString::to_string)
+    .collect();
+    assert_eq!(
+        commands,
+        ["Down 3", "Enter", "Tab", "Tab", "Down 3", "Enter", "Tab", "Tab", "Sleep 1s"]
+    );
+    let tape = "Call item\nDefine item {\n}\nDefine item {\n}\n}\nRepeat 2 {\n";
+    let err = Parser::parse(
+        tape,
+        "loop.tape",
+        Path::new(""),
+        Vec::new(),
+        Vars::default(),
+    )
+    .err()
+    .unwrap();
+    let diagnostics = err.downcast_ref::<error::Diagnostics>().unwrap();
+    let lines: Vec<_> = diagnostics.0.iter().map(|d| d.line).collect();
+    assert_eq!(lines, [1, 4, 6, 7]);
+    // once, where it goes past the limit
+    let too_long = |tape| {
+        let err = parse(tape).err().unwrap();
+        let diagnostics = err.downcast_ref::<error::Diagnostics>().unwrap();
+        diagnostics.0.iter().map(|d| d.line).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        too_long(
+            "Tab
+Repeat 1000 {
+Repeat 1000 {
+Repeat 1000 {
+Tab
+}
+}
+}
+"
+        ),
+        [3]
+    );
+    assert_eq!(
+        too_long(
+            "Repeat 2000000 {
+}
+"
+        ),
+        [1]
+    );
+    let tape = "Define item {
+Repeat 1000 {
+Tab
+}
+}
+Repeat 1000 {
+Call item
+}
+";
+    assert_eq!(too_long(tape), [6]);
+    let tape = "Define item {
+Repeat 600000 {
+}
+}
+Call item
+Call item
+Call item
+";
+    assert_eq!(too_long(tape), [6]);
+}
@@ -0,0 +1,49 @@
+//! `Repeat` and `Define`/`Call`, which nest, so a tape is a tree until it's
+//! expanded into plain commands
+use super::Commands;
+use std::collections::HashMap;
+
+/// Steps a tape may expand to, one for each command and for each time around
+/// a `Repeat`, so that nested `Repeat`s can't take all the memory
+pub const MAX_LEN: usize = 1_000_000;
+
+pub enum Node {
+    Command(Commands),
+    /// `Repeat N { ... }`
+    Repeat(usize, Vec<Node>),
+    /// `Call name`, which is always `Define`d by then
+    Call(String),
+}
+
+/// Nodes, and the steps they expand to
+#[derive(Default)]
+pub struct Body {
+    pub nodes: Vec<Node>,
+    pub len: usize,
+}
+impl Body {
+    /// Adds `node`, which expands to `len` steps. True when that's what takes
+    /// the body past [`MAX_LEN`].
+    pub fn push(&mut self, node: Node, len: usize) -> bool {
+        let before = self.len;
+        self.len = before.saturating_add(len);
+        self.nodes.push(node);
+        before <= MAX_LEN && self.len > MAX_LEN
+    }
+}
+
+/// `macros` is every `Define name { ... }`
+pub fn expand(nodes: &[Node], macros: &HashMap<String, Body>, out: &mut Vec<Commands>) {
+    for node in nodes {
+        match node {
+            Node::Command(cmd) => out.push(cmd.clone()),
+            Node::Repeat(times, body) => {
+                for _ in 0..*times {
+                    expand(body, macros, out);
+                }
+            }
+            // `Parser::include` only takes a `Call` once its macro is defined
+            Node::Call(name) => expand(&macros[name].nodes, macros, out),
+        }
+    }
+}
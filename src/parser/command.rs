@@ -1,9 +1,16 @@
 use super::error::ParseError;
 use crate::utils::key2bytes::{self as Keys, U8Code as Key};
 
+#[derive(Clone)]
 pub enum Commands {
     Output(String),
     Set(super::env::EnVar),
+    /// (key, value) for the child
+    Env(String, String),
+    /// Another tape, inlined while parsing
+    Source(String),
+    /// (name, value) for `${name}` from here on
+    Let(String, String),
 
     Enter(usize, Option<u64>),
     Escape(usize, Option<u64>),
@@ -17,12 +24,52 @@ pub enum Commands {
     BackSpace(usize, Option<u64>),
 
     Sleep(u64),
+    /// Stop recording, the tape still runs and shows on screen
+    Hide,
+    /// Record again, starting with the whole screen
+    Show,
+
+    /// `Repeat N {`, up to the matching `}`
+    Repeat(usize),
+    /// `Define name {`, up to the matching `}`
+    Define(String),
+    /// `}`
+    End,
+    Call(String),
 
     Type(String, Option<u64>),
+    /// `Type <<EOF` or `Paste <<EOF`, with the lines up to `EOF` taken as is.
+    /// Typed with an Enter between the lines, or pasted all at once for
+    /// programs which turned on bracketed paste.
+    Block {
+        lines: Vec<String>,
+        delimiter: String,
+        delay: Option<u64>,
+        paste: bool,
+    },
     Null,
 }
+/// What running a tape comes down to
+pub enum Step {
+    Key(Key),
+    Hide,
+    Show,
+}
+
 impl Commands {
-    pub fn into_key(self, default_delay: u64) -> Vec<(Key, u64)> {
+    pub fn into_steps(self, typing: &mut super::Typing) -> Vec<(Step, u64)> {
+        match self {
+            Commands::Hide => vec![(Step::Hide, 0)],
+            Commands::Show => vec![(Step::Show, 0)],
+            cmd => cmd
+                .into_key(typing)
+                .into_iter()
+                .map(|(key, delay)| (Step::Key(key), delay))
+                .collect(),
+        }
+    }
+    /// `Type` goes out one grapheme at a time
+    pub fn into_key(self, typing: &mut super::Typing) -> Vec<(Key, u64)> {
         fn repeat_with_delay(
             key: Key,
             delay: Option<u64>,
@@ -32,9 +79,19 @@ impl Commands {
             let delay = delay.unwrap_or(default_delay);
             vec![(key, delay); times]
         }
+        fn type_text(text: &str, delay: u64, typing: &mut super::Typing) -> Vec<(Key, u64)> {
+            use unicode_segmentation::UnicodeSegmentation;
+            text.graphemes(true)
+                .map(|g| (Key::Auto(g.as_bytes().to_vec()), typing.delay(delay)))
+                .collect()
+        }
+        let default_delay = typing.speed;
         match self {
-            Commands::Output(_) => unimplemented!(),
-            Commands::Set(_) => unimplemented!(),
+            Commands::Output(_)
+            | Commands::Set(_)
+            | Commands::Env(..)
+            | Commands::Source(_)
+            | Commands::Let(..) => unreachable!("consumed by Parser::include"),
 
             Commands::Enter(times, delay) => {
                 repeat_with_delay(Key::Ascii(Keys::ascii::ENTER), delay, default_delay, times)
@@ -73,21 +130,48 @@ impl Commands {
                 Key::Ascii(Keys::ascii::NULL),
                 length,
             )],
+            Commands::Hide | Commands::Show => unreachable!("turned into steps by into_steps"),
+            Commands::Repeat(_) | Commands::Define(_) | Commands::End | Commands::Call(_) => {
+                unreachable!("consumed by Parser::include")
+            }
 
-            Commands::Type(s, sp) => {
-                vec![(Key::Auto(s.into_bytes()), sp.unwrap_or(default_delay))]
+            Commands::Type(s, sp) => type_text(&s, sp.unwrap_or(default_delay), typing),
+            Commands::Block {
+                lines,
+                delay,
+                paste: true,
+                ..
+            } => {
+                let text = format!("\x1b[200~{}\x1b[201~", lines.join("\r"));
+                vec![(Key::Auto(text.into_bytes()), delay.unwrap_or(default_delay))]
+            }
+            Commands::Block { lines, delay, .. } => {
+                let delay = delay.unwrap_or(default_delay);
+                let mut keys = Vec::new();
+                for (idx, line) in lines.iter().enumerate() {
+                    if idx > 0 {
+                        keys.push((Key::Ascii(Keys::ascii::ENTER), typing.delay(delay)));
+                    }
+                    keys.extend(type_text(line, delay, typing));
+                }
+                keys
             }
-            Commands::Null => unimplemented!(),
+            Commands::Null => unreachable!("consumed by Parser::include"),
         }
     }
 }
 impl core::fmt::Display for Commands {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use super::utils::format_sleep;
+        fn format_delay(time: &Option<u64>) -> String {
+            time.map(|s| format!("@{}", format_sleep(s)))
+                .unwrap_or_default()
+        }
         fn format_command(cmd: &str, times: &usize, time: &Option<u64>) -> std::string::String {
-            format!(
-                "{cmd}{} {times}",
-                time.map(|s| format!("@{s}ms")).unwrap_or_default()
-            )
+            match times {
+                1 => format!("{cmd}{}", format_delay(time)),
+                _ => format!("{cmd}{} {times}", format_delay(time)),
+            }
         }
         write!(
             f,
@@ -95,6 +179,14 @@ impl core::fmt::Display for Commands {
             match self {
                 Commands::Output(f) => format!("Output {f}"),
                 Commands::Set(v) => format!("Set {v}"),
+                Commands::Env(k, v) => format!("Env {k} {v}"),
+                Commands::Source(path) => format!("Source {path}"),
+                Commands::Let(name, value) => match super::string::parse_text(value) {
+                    Ok(text) if text == *value && !value.is_empty() => {
+                        format!("Let {name} {value}")
+                    }
+                    _ => format!("Let {name} {}", super::string::quote(value)),
+                },
 
                 Commands::Enter(n, sp) => format_command("Enter", n, sp),
                 Commands::Escape(n, sp) => format_command("Escape", n, sp),
@@ -105,20 +197,39 @@ impl core::fmt::Display for Commands {
                 Commands::Left(n, sp) => format_command("Left", n, sp),
                 Commands::Right(n, sp) => format_command("Right", n, sp),
                 Commands::BackSpace(n, sp) => format_command("BackSpace", n, sp),
-                Commands::Sleep(v) => format!("Sleep {v}ms"),
+                Commands::Sleep(v) => format!("Sleep {}", format_sleep(*v)),
+                Commands::Hide => "Hide".to_string(),
+                Commands::Show => "Show".to_string(),
+                Commands::Repeat(times) => format!("Repeat {times} {{"),
+                Commands::Define(name) => format!("Define {name} {{"),
+                Commands::End => "}".to_string(),
+                Commands::Call(name) => format!("Call {name}"),
 
-                Commands::Type(v, sp) => format!(
-                    "Type{} \"{v}\"",
-                    sp.map(|s| format!("@{s}ms")).unwrap_or_default()
+                Commands::Type(v, sp) => {
+                    format!("Type{} {}", format_delay(sp), super::string::quote(v))
+                }
+                Commands::Block {
+                    lines,
+                    delimiter,
+                    delay,
+                    paste,
+                } => format!(
+                    "{}{} <<{delimiter}\n{}{delimiter}",
+                    if *paste { "Paste" } else { "Type" },
+                    format_delay(delay),
+                    lines.iter().map(|l| format!("{l}\n")).collect::<String>()
                 ),
                 Commands::Null => return Err(std::fmt::Error),
             }
         )
     }
 }
-const COMMANDS: [&str; 14] = [
+const COMMANDS: [&str; 24] = [
     "Output ",
     "Set ",
+    "Env ",
+    "Source ",
+    "Let ",
     "Enter",
     "Escape",
     "Tab",
@@ -129,9 +240,23 @@ const COMMANDS: [&str; 14] = [
     "Right",
     "BackSpace",
     "Sleep ",
+    "Hide",
+    "Show",
+    "Repeat ",
+    "Define ",
+    "Call ",
+    "}",
     "Type",
+    "Paste",
     "#",
 ];
+/// The names in [`COMMANDS`], for diagnostics
+pub fn names() -> impl Iterator<Item = &'static str> {
+    COMMANDS
+        .iter()
+        .map(|c| c.trim_end())
+        .filter(|c| c.starts_with(char::is_alphabetic))
+}
 impl core::str::FromStr for Commands {
     type Err = ParseError;
 
@@ -149,7 +274,24 @@ impl core::str::FromStr for Commands {
             return match pat {
                 "Output " => Ok(Commands::Output(s.into())),
                 "Set " => s.parse().map(Commands::Set),
+                "Env " => s
+                    .split_once(char::is_whitespace)
+                    .map(|(k, v)| Commands::Env(k.to_string(), v.trim_start().to_string()))
+                    .ok_or_else(|| {
+                        ParseError(Box::from(
+                            "Failed to parse `Env`, make sure it's like `Env KEY value`",
+                        ))
+                    }),
 
+                "Source " => Ok(Commands::Source(s.into())),
+                "Let " => match s.split_once(char::is_whitespace).unwrap_or((s, "")) {
+                    (name, value) if super::vars::is_name(name) => {
+                        Ok(Commands::Let(name.to_string(), super::string::parse_text(value)?))
+                    }
+                    _ => Err(ParseError(Box::from(
+                        "Failed to parse `Let`, make sure it's like `Let NAME value`",
+                    ))),
+                },
                 "Enter" => Ok(parse_with_delay_times(s).map(|(s,n)|Commands::Enter(s, n))?),
                 "Escape" => Ok(parse_with_delay_times(s).map(|(s,n)|Commands::Escape(s,n))?),
                 "Tab" => Ok(parse_with_delay_times(s).map(|(s,n)|Commands::Tab(s, n))?),
@@ -166,22 +308,82 @@ impl core::str::FromStr for Commands {
                             "Failed to parse `Sleep`, make sure it's like `Sleep 500ms`/`Sleep 1s`",
                         ))
                     }),
-                "Type" => parse_with_delay_or(s).map(|(s,n)|Commands::Type(s.to_string(), n))
-                    .map_err(|_| {
+                "Hide" | "Show" if !s.is_empty() => Err(ParseError(
+                    format!("`{pat}` takes no arguments").into(),
+                )),
+                "Repeat " => s
+                    .strip_suffix('{')
+                    .and_then(|n| n.trim().parse().ok())
+                    .map(Commands::Repeat)
+                    .ok_or_else(|| {
+                        ParseError(Box::from(
+                            "Failed to parse `Repeat`, make sure it's like `Repeat 3 {`",
+                        ))
+                    }),
+                "Define " => s
+                    .strip_suffix('{')
+                    .map(str::trim)
+                    .filter(|name| super::vars::is_name(name))
+                    .map(|name| Commands::Define(name.to_string()))
+                    .ok_or_else(|| {
                         ParseError(Box::from(
-                            "Failed to parse `Type`, make sure it's like `Type@200ms \"test\"`/`Type@0.1s` \"test\" or `Type \"test\"`",
+                            "Failed to parse `Define`, make sure it's like `Define name {`",
                         ))
                     }),
+                "Call " if super::vars::is_name(s) => Ok(Commands::Call(s.to_string())),
+                "Call " => Err(ParseError(Box::from(
+                    "Failed to parse `Call`, make sure it's like `Call name`",
+                ))),
+                "}" if !s.is_empty() => Err(ParseError(Box::from("`}` goes on a line of its own"))),
+                "}" => Ok(Commands::End),
+                "Hide" => Ok(Commands::Hide),
+                "Show" => Ok(Commands::Show),
+                "Type" | "Paste" => match (block(s), pat) {
+                    (Some(block), _) => block.map(|(delay, delimiter)| Commands::Block {
+                        lines: Vec::new(),
+                        delimiter: delimiter.to_string(),
+                        delay,
+                        paste: pat == "Paste",
+                    }),
+                    (None, "Paste") => Err(ParseError(Box::from(
+                        "`Paste` takes a block, make sure it's like `Paste <<EOF`",
+                    ))),
+                    (None, _) => parse_with_delay_or(s)
+                        .map_err(|_| {
+                            ParseError(Box::from(
+                                "Failed to parse `Type`, make sure it's like `Type@200ms \"test\"`/`Type@0.1s` \"test\" or `Type \"test\"`",
+                            ))
+                        })
+                        .and_then(|(s, n)| Ok(Commands::Type(super::string::parse_text(s)?, n))),
+                },
                 "#" => Ok(Commands::Null),
                 _ => unreachable!(),
             };
         }
         Err(ParseError(
-            format!("Failed to parse Commands`{s}`, what's that?").into(),
+            format!(
+                "unknown command `{}`",
+                s.split_whitespace().next().unwrap_or(s)
+            )
+            .into(),
         ))
     }
 }
 
+/// `@100ms <<EOF` or `<<EOF`, if `s` opens a block at all
+fn block(s: &str) -> Option<Result<(Option<u64>, &str), ParseError>> {
+    let (rest, delay) = super::utils::parse_with_delay_or(s).ok()?;
+    let delimiter = rest.strip_prefix("<<")?.trim();
+    Some(
+        match delimiter.is_empty() || delimiter.contains(char::is_whitespace) {
+            true => Err(ParseError(Box::from(
+                "the block needs one word to end it, like `<<EOF`",
+            ))),
+            false => Ok((delay, delimiter)),
+        },
+    )
+}
+
 #[cfg(test)]
 #[test]
 fn test() {
@@ -190,17 +392,34 @@ fn test() {
 Type \"ttytape\"
 Sleep 500ms
 Type@100ms \"ttyrec\"
+Type@1s 'say \"hi\" to `s`\\t'
 
 Set TypingSpeed 100ms
 Set FontSize 17
 Set Width 81
 Set Height 41
+Set WorkingDirectory /tmp
+Set Term xterm-256color
+Env COLORTERM truecolor
+Source common.tape
+Let bin \"vtyrec\"
 Enter 2
 Sleep 1s
+Hide
+Define menu {
+Down@100ms 3
+Enter
+}
+Repeat 2 {
+Call menu
+}
 Tab
+Show
 Escape@100ms
 BackSpace@0.1s 3
 ";
+    // kept as it was written, clippy only came to prefer `matches!` later
+    #[allow(clippy::match_like_matches_macro)]
     fn print(tape: &str) {
         tape.lines()
             .inspect(|s| print!("{s} => "))
@@ -5,17 +5,29 @@ use super::error::ParseError;
 pub struct Envs {
     // unify to ms
     pub typingspeed: u64,
+    /// in percent
+    pub typing_jitter: u8,
+    pub typing_seed: u64,
     pub file_name: String,
     pub shell: String,
     pub size: (u16, u16),
+    pub working_directory: Option<String>,
+    pub term: Option<String>,
+    /// from `Env`, in order
+    pub vars: Vec<(String, String)>,
 }
 impl Default for Envs {
     fn default() -> Self {
         Self {
             typingspeed: 200,
+            typing_jitter: 0,
+            typing_seed: 0,
             file_name: DEFAULT_FILE_NAME.to_string(),
             shell: DEFAULT_SHELL.to_string(),
             size: (16, 80),
+            working_directory: None,
+            term: None,
+            vars: Vec::new(),
         }
     }
 }
@@ -23,34 +35,76 @@ impl Envs {
     pub fn set(&mut self, new: EnVar) {
         match new {
             EnVar::TypingSpeed(s) => self.typingspeed = s,
+            EnVar::TypingJitter(j) => self.typing_jitter = j,
+            EnVar::TypingSeed(s) => self.typing_seed = s,
             EnVar::Shell(s) => self.shell = s,
-            EnVar::FontSize(_) => unimplemented!("Not support"),
+            EnVar::FontSize(_) => unreachable!("a diagnostic in Parser::include"),
             EnVar::Width(w) => self.size.1 = w,
             EnVar::Height(h) => self.size.0 = h,
+            EnVar::WorkingDirectory(d) => self.working_directory = Some(d),
+            EnVar::Term(t) => self.term = Some(t),
+        }
+    }
+    pub fn typing(&self) -> super::Typing {
+        super::Typing::new(self.typingspeed, self.typing_jitter, self.typing_seed)
+    }
+    pub fn apply(&self, cmd: &mut portable_pty::CommandBuilder) {
+        for (key, value) in &self.vars {
+            cmd.env(key, value);
+        }
+        if let Some(term) = &self.term {
+            cmd.env("TERM", term);
+        }
+        if let Some(dir) = &self.working_directory {
+            cmd.cwd(dir);
         }
     }
 }
 
+#[derive(Clone)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 pub enum EnVar {
     TypingSpeed(u64),
+    /// in percent, up to 100
+    TypingJitter(u8),
+    TypingSeed(u64),
     Shell(String),
     FontSize(u8),
     Width(u16),
     Height(u16),
+    WorkingDirectory(String),
+    Term(String),
 }
 impl core::fmt::Display for EnVar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            EnVar::TypingSpeed(v) => write!(f, "TypingSpeed {v}"),
+            EnVar::TypingSpeed(v) => write!(f, "TypingSpeed {}", super::utils::format_sleep(*v)),
+            EnVar::TypingJitter(j) => write!(f, "TypingJitter {j}%"),
+            EnVar::TypingSeed(s) => write!(f, "TypingSeed {s}"),
             EnVar::Shell(s) => write!(f, "Shell {s}"),
             EnVar::FontSize(n) => write!(f, "FontSize {n}"),
             EnVar::Width(w) => write!(f, "Width {w}"),
             EnVar::Height(h) => write!(f, "Height {h}"),
+            EnVar::WorkingDirectory(d) => write!(f, "WorkingDirectory {d}"),
+            EnVar::Term(t) => write!(f, "Term {t}"),
         }
     }
 }
-const ENVS: [&str; 5] = ["TypingSpeed ", "Shell ", "FontSize ", "Width ", "Height "];
+const ENVS: [&str; 9] = [
+    "TypingSpeed ",
+    "TypingJitter ",
+    "TypingSeed ",
+    "Shell ",
+    "FontSize ",
+    "Width ",
+    "Height ",
+    "WorkingDirectory ",
+    "Term ",
+];
+/// The names in [`ENVS`], for diagnostics
+pub fn names() -> impl Iterator<Item = &'static str> {
+    ENVS.iter().map(|e| e.trim_end())
+}
 impl core::str::FromStr for EnVar {
     type Err = ParseError;
 
@@ -62,15 +116,32 @@ impl core::str::FromStr for EnVar {
             let s = s.strip_prefix(pat).unwrap().trim();
             return match pat {
                 "TypingSpeed "=>super::utils::parse_sleep(s).map(Self::TypingSpeed).map_err(|_|ParseError(Box::from("Failed to parse `TypingSpeed`, make sure it's like `Set TypingSpeed 500ms`"))),
+                "TypingJitter " => s
+                    .strip_suffix('%')
+                    .and_then(|j| j.trim().parse::<u8>().ok())
+                    .filter(|j| *j <= 100)
+                    .map(Self::TypingJitter)
+                    .ok_or_else(|| {
+                        ParseError(Box::from(
+                            "Failed to parse `TypingJitter`, make sure it's like `Set TypingJitter 30%`, up to 100%",
+                        ))
+                    }),
+                "TypingSeed " => s.parse::<u64>().map(Self::TypingSeed).map_err(|e|ParseError(e.to_string().into_boxed_str())),
                 "Shell " => Ok(Self::Shell(s.trim().to_string())),
                 "FontSize " => s.parse::<u8>().map(Self::FontSize).map_err(|e|ParseError(e.to_string().into_boxed_str())),
                 "Width " => s.parse::<u16>().map(Self::Width).map_err(|e|ParseError(e.to_string().into_boxed_str())),
                 "Height "=>s.parse::<u16>().map(Self::Height).map_err(|e|ParseError(e.to_string().into_boxed_str())),
+                "WorkingDirectory " => Ok(Self::WorkingDirectory(s.to_string())),
+                "Term " => Ok(Self::Term(s.to_string())),
                 _=>unreachable!()
             };
         }
         Err(ParseError(
-            format!("Failed to parse WorkEnv`{s}`, what's that?").into(),
+            format!(
+                "unknown setting `{}`",
+                s.split_whitespace().next().unwrap_or(s)
+            )
+            .into(),
         ))
     }
 }
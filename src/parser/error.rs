@@ -21,3 +21,172 @@ impl From<core::num::ParseFloatError> for ParseError {
         Self(value.to_string().into_boxed_str())
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+/// A [`ParseError`] along with where it happened, shown like rustc does
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    /// in chars, 0-based
+    pub span: std::ops::Range<usize>,
+    pub source: String,
+    pub message: Box<str>,
+    pub help: Option<String>,
+}
+impl Diagnostic {
+    /// The span and the suggestion are worked out from the line itself:
+    /// an unknown command or setting gets underlined with the closest known
+    /// one suggested, otherwise the arguments are underlined.
+    pub fn new(file: &str, line: usize, source: &str, error: ParseError) -> Self {
+        let words = words(source);
+        let did_you_mean = |s| format!("did you mean `{s}`?");
+        let (span, help) = match words.as_slice() {
+            [] => (0..source.chars().count(), None),
+            [(word, name), ..] if !super::command::names().any(|n| n == *name) => (
+                word.clone(),
+                suggest(name, super::command::names()).map(did_you_mean),
+            ),
+            [(_, "Set"), (word, name), ..] if !super::env::names().any(|n| n == *name) => (
+                word.clone(),
+                suggest(name, super::env::names()).map(did_you_mean),
+            ),
+            [(_, "Set"), _, rest @ ..] | [_, rest @ ..] if !rest.is_empty() => {
+                (rest[0].0.start..rest[rest.len() - 1].0.end, None)
+            }
+            [(word, _), ..] => (word.clone(), None),
+        };
+        Self {
+            level: Level::Error,
+            file: file.to_string(),
+            line,
+            span,
+            source: source.to_string(),
+            message: error.0,
+            help,
+        }
+    }
+    /// About the whole line, for problems found after parsing
+    pub fn lint(level: Level, file: &str, line: usize, source: &str, message: String) -> Self {
+        let start = source.chars().take_while(|c| c.is_whitespace()).count();
+        let end = source.trim_end().chars().count();
+        Self {
+            level,
+            file: file.to_string(),
+            line,
+            span: start..end.max(start),
+            source: source.to_string(),
+            message: message.into(),
+            help: None,
+        }
+    }
+    pub fn help(self, help: impl Into<String>) -> Self {
+        Self {
+            help: Some(help.into()),
+            ..self
+        }
+    }
+}
+impl core::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let number = self.line.to_string();
+        let pad = " ".repeat(number.len());
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        writeln!(f, "{level}: {}", self.message)?;
+        writeln!(
+            f,
+            "{pad}--> {}:{}:{}",
+            self.file,
+            self.line,
+            self.span.start + 1
+        )?;
+        writeln!(f, "{pad} |")?;
+        writeln!(f, "{number} | {}", self.source)?;
+        write!(
+            f,
+            "{pad} | {}{}",
+            " ".repeat(self.span.start),
+            "^".repeat(self.span.len().max(1))
+        )?;
+        if let Some(help) = &self.help {
+            write!(f, " {help}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every error of a tape, so they can be fixed in one go
+#[derive(Debug)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+impl core::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in &self.0 {
+            writeln!(f, "{diagnostic}\n")?;
+        }
+        match self.0.len() {
+            1 => write!(f, "aborting due to the previous error"),
+            n => write!(f, "aborting due to {n} previous errors"),
+        }
+    }
+}
+impl std::error::Error for Diagnostics {}
+
+/// (char span, word), split at whitespace, `@` and `"` so that `Type@1s`
+/// gives `Type` first
+fn words(line: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut chars = line.char_indices().enumerate().peekable();
+    while let Some((col, (idx, ch))) = chars.next() {
+        let sep = ch.is_whitespace() || (words.is_empty() && matches!(ch, '@' | '"'));
+        match (start, sep) {
+            (None, false) => start = Some((col, idx)),
+            (Some((col_start, idx_start)), true) => {
+                words.push((col_start..col, &line[idx_start..idx]));
+                start = None;
+            }
+            _ => (),
+        }
+        if chars.peek().is_none() {
+            if let Some((col_start, idx_start)) = start {
+                words.push((col_start..col + 1, &line[idx_start..]));
+            }
+        }
+    }
+    words
+}
+
+/// The closest candidate, if it's close enough to be a typo
+fn suggest<'a>(word: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let lower = word.to_lowercase();
+    candidates
+        .map(|c| (distance(&lower, &c.to_lowercase()), c))
+        .filter(|(d, c)| *d <= (c.len() / 3).max(1))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut now = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != *cb);
+            now.push(substitute.min(prev[j + 1] + 1).min(now[j] + 1));
+        }
+        prev = now;
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,132 @@
+//! String literals of `Type`
+//!
+//! `"..."`, `'...'` and `` `...` `` all take the same backslash escapes:
+//! `\n`, `\r`, `\t`, `\e`, `\0`, `\\`, any of the quotes, `\x1b` up to `\x7f`
+//! and `\u{1F600}`. Text without quotes is taken as is.
+use super::error::ParseError;
+
+pub const QUOTES: [char; 3] = ['"', '\'', '`'];
+
+/// The whole of `s` is the text, quoted or not
+pub fn parse_text(s: &str) -> Result<String, ParseError> {
+    let s = s.trim();
+    if !s.starts_with(QUOTES) {
+        return Ok(s.to_string());
+    }
+    let (text, rest) = parse_literal(s)?;
+    match rest.trim() {
+        "" => Ok(text),
+        rest => Err(ParseError(
+            format!("unexpected `{rest}` after the string").into(),
+        )),
+    }
+}
+
+/// Unescape the literal `s` starts with, and return what's after it
+pub fn parse_literal(s: &str) -> Result<(String, &str), ParseError> {
+    let mut chars = s.char_indices();
+    let quote = match chars.next() {
+        Some((_, ch)) if QUOTES.contains(&ch) => ch,
+        _ => return Err(ParseError(Box::from("expected a string"))),
+    };
+    let mut text = String::new();
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            _ if ch == quote => return Ok((text, &s[idx + ch.len_utf8()..])),
+            '\\' => text.push(unescape(&mut chars)?),
+            _ => text.push(ch),
+        }
+    }
+    Err(ParseError(
+        format!("unterminated string, missing the closing {quote}").into(),
+    ))
+}
+
+fn unescape(chars: &mut std::str::CharIndices) -> Result<char, ParseError> {
+    let invalid = |what: &str| ParseError(format!("invalid escape `\\{what}`").into());
+    let Some((_, ch)) = chars.next() else {
+        return Err(ParseError(Box::from("unterminated string")));
+    };
+    Ok(match ch {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'e' => '\x1b',
+        '0' => '\0',
+        '\\' | '"' | '\'' | '`' => ch,
+        'x' => {
+            let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+            u8::from_str_radix(&hex, 16)
+                .ok()
+                .filter(|b| b.is_ascii() && hex.len() == 2)
+                .map(char::from)
+                .ok_or_else(|| invalid(&format!("x{hex}")))?
+        }
+        'u' => {
+            let mut code = String::new();
+            if chars.next().map(|(_, c)| c) != Some('{') {
+                return Err(invalid("u"));
+            }
+            for (_, c) in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+                code.push(c);
+            }
+            u32::from_str_radix(&code, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| invalid(&format!("u{{{code}}}")))?
+        }
+        _ => return Err(invalid(&ch.to_string())),
+    })
+}
+
+/// A `"..."` literal which [`parse_literal`] gives `text` back from
+pub fn quote(text: &str) -> String {
+    let mut out = String::from('"');
+    for ch in text.chars() {
+        match ch {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\x1b' => out.push_str("\\e"),
+            _ if ch.is_ascii_control() => out.push_str(&format!("\\x{:02x}", ch as u8)),
+            _ if ch.is_control() => out.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let ok = |s: &str| parse_text(s).unwrap();
+    assert_eq!(ok(r#""say \"hi\"""#), r#"say "hi""#);
+    assert_eq!(ok(r#"'it\'s'"#), "it's");
+    assert_eq!(ok(r#"`echo "$s" 'x'`"#), r#"echo "$s" 'x'"#);
+    assert_eq!(ok(r#""a\tb\nc\\""#), "a\tb\nc\\");
+    assert_eq!(ok(r#""\x1b[31mred\e[0m""#), "\x1b[31mred\x1b[0m");
+    assert_eq!(ok(r#""\u{1F600} ünï""#), "😀 ünï");
+    assert_eq!(ok("  ls -la  "), "ls -la");
+    assert_eq!(ok(r#""""#), "");
+    for bad in [
+        r#""open"#,
+        r#""\q""#,
+        r#""\x8""#,
+        r#""\xff""#,
+        r#""\u{110000}""#,
+        r#""a" b"#,
+    ] {
+        assert!(parse_text(bad).is_err(), "{bad}");
+    }
+    for text in ["say \"hi\"\\", "tab\there\r\n", "\x1b[0m\x07", "😀\u{85}"] {
+        assert_eq!(ok(&quote(text)), text);
+    }
+}
@@ -0,0 +1,49 @@
+//! When each keystroke of `Type` goes out
+
+/// `TypingSpeed`, give or take `TypingJitter`. The same seed always gives
+/// the same intervals, so a tape records the same way every time.
+pub struct Typing {
+    pub speed: u64,
+    /// in percent of the delay
+    jitter: u8,
+    state: u64,
+}
+impl Typing {
+    pub fn new(speed: u64, jitter: u8, seed: u64) -> Self {
+        Self {
+            speed,
+            jitter: jitter.min(100),
+            state: seed,
+        }
+    }
+    /// Somewhere in `delay` ± the jitter
+    pub fn delay(&mut self, delay: u64) -> u64 {
+        let spread = delay * self.jitter as u64 / 100;
+        if spread == 0 {
+            return delay;
+        }
+        delay - spread + self.next() % (spread * 2 + 1)
+    }
+    /// splitmix64
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let delays = |seed| {
+        let mut typing = Typing::new(100, 30, seed);
+        (0..50).map(|_| typing.delay(100)).collect::<Vec<_>>()
+    };
+    assert_eq!(delays(7), delays(7));
+    assert_ne!(delays(7), delays(8));
+    assert!(delays(7).iter().all(|d| (70..=130).contains(d)));
+    assert!(delays(7).iter().any(|d| *d != 100));
+    assert_eq!(Typing::new(100, 0, 7).delay(100), 100);
+}
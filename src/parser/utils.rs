@@ -10,6 +10,15 @@ pub fn parse_sleep(s: &str) -> Result<u64, ParseError> {
     }
 }
 
+/// The other way around of [`parse_sleep`], `2s` when it's whole seconds
+/// and `1500ms` otherwise
+pub fn format_sleep(ms: u64) -> String {
+    match ms % 1000 {
+        0 if ms > 0 => format!("{}s", ms / 1000),
+        _ => format!("{ms}ms"),
+    }
+}
+
 pub fn parse_times(s: &str) -> Result<usize, core::num::ParseIntError> {
     if !s.is_empty() {
         Ok(s.parse()?)
@@ -37,23 +46,30 @@ pub fn parse_with_delay_times(s: &str) -> Result<(usize, Option<u64>), ParseErro
 ///
 /// (content, delay)
 fn parse_delay(s: &str) -> Result<(&str, u64), ParseError> {
-    let mut rel = s.split('s');
-    let maybe_delay = rel.next().unwrap();
+    // the content may start with a quote right away, as in `Type@1s"ls"`
+    let end = s
+        .find(|c: char| c.is_whitespace() || super::string::QUOTES.contains(&c))
+        .unwrap_or(s.len());
+    let (maybe_delay, rest) = s.split_at(end);
     Ok((
-        rel.next()
-            .unwrap_or_default()
-            .trim_start()
-            .trim_start_matches('"')
-            .trim_end_matches('"'),
-        match maybe_delay.strip_suffix('m') {
-            Some(s) => s.parse()?,
-            None => maybe_delay
+        rest.trim_start(),
+        match (
+            maybe_delay.strip_suffix("ms"),
+            maybe_delay.strip_suffix('s'),
+        ) {
+            (Some(s), _) => s.parse()?,
+            (None, Some(s)) => s
                 .parse()
                 .map(|n: f64| n * 1000.0)
                 // Ok when value is not very big, otherwise it might be incorrect
                 // as 1e50_f64 => 18446744073709551615
                 // but it's fine
                 .map(|n| n.trunc() as u64)?,
+            (None, None) => {
+                return Err(ParseError(
+                    format!("`{maybe_delay}` needs a unit, like `100ms` or `0.1s`").into(),
+                ))
+            }
         },
     ))
 }
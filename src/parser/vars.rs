@@ -0,0 +1,140 @@
+//! `${NAME}` in `Type`, `Output`, `Set` and `Let`, and in the lines of a
+//! `Type <<EOF` or `Paste <<EOF` block
+use super::{command::Commands, error::ParseError};
+use std::collections::HashMap;
+
+/// The commands which take `${NAME}`
+const INTERPOLATED: [&str; 4] = ["Type", "Output ", "Set ", "Let "];
+
+/// `--var` comes first, then `Let`, then the environment
+pub struct Vars {
+    overrides: HashMap<String, String>,
+    lets: HashMap<String, String>,
+    env: fn(&str) -> Option<String>,
+}
+impl Default for Vars {
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+impl Vars {
+    pub fn new(overrides: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            overrides: overrides.into_iter().collect(),
+            lets: HashMap::new(),
+            env: |name| std::env::var(name).ok(),
+        }
+    }
+    /// Looks up the environment with `env`, as tests can't set the real one
+    /// while others run
+    #[cfg(test)]
+    fn with_env(self, env: fn(&str) -> Option<String>) -> Self {
+        Self { env, ..self }
+    }
+    pub fn set(&mut self, name: String, value: String) {
+        self.lets.insert(name, value);
+    }
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.overrides
+            .get(name)
+            .or_else(|| self.lets.get(name))
+            .cloned()
+            .or_else(|| (self.env)(name))
+    }
+    /// Parse `line`, then replace `${NAME}` in what it gives, so that a value
+    /// with quotes or backslashes is never read as a part of the tape.
+    /// `Set` takes no quotes, so its line is interpolated as is.
+    pub fn parse(&self, line: &str) -> Result<Commands, ParseError> {
+        if !uses_vars(line) {
+            return line.parse();
+        }
+        if line.trim_start().starts_with("Set ") {
+            return self.interpolate(line)?.parse();
+        }
+        Ok(match line.parse()? {
+            Commands::Type(text, delay) => Commands::Type(self.interpolate(&text)?.into(), delay),
+            Commands::Output(path) => Commands::Output(self.interpolate(&path)?.into()),
+            Commands::Let(name, value) => Commands::Let(name, self.interpolate(&value)?.into()),
+            cmd => cmd,
+        })
+    }
+    /// `text` with every `${NAME}` replaced, `$${NAME}` is left as `${NAME}`
+    pub fn interpolate<'a>(&self, text: &'a str) -> Result<std::borrow::Cow<'a, str>, ParseError> {
+        if !text.contains("${") {
+            return Ok(text.into());
+        }
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                out.push_str(&rest[..start - 1]);
+                out.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            out.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                return Err(ParseError(Box::from("unterminated `${`, missing the `}`")));
+            };
+            let name = &rest[start + 2..start + end];
+            match self.get(name) {
+                Some(value) => out.push_str(&value),
+                None => {
+                    return Err(ParseError(
+                        format!("undefined variable `{name}`, `Let` it or pass `--var {name}=...`")
+                            .into(),
+                    ))
+                }
+            }
+            rest = &rest[start + end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out.into())
+    }
+}
+
+/// Whether [`Vars::interpolate`] would touch `line`
+pub fn uses_vars(line: &str) -> bool {
+    let line = line.trim_start();
+    INTERPOLATED.iter().any(|cmd| line.starts_with(cmd)) && line.contains("${")
+}
+
+/// A letter or `_`, then letters, digits and `_`
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let mut vars = Vars::new([("bin".to_string(), "vtyrec".to_string())])
+        .with_env(|name| (name == "HOME").then(|| "/home/me".to_string()));
+    vars.set("bin".into(), "ttyrec".into());
+    vars.set("version".into(), "1.2".into());
+    vars.set("width".into(), "120".into());
+    vars.set("msg".into(), "say \"hi\"".into());
+    vars.set("dir".into(), "C:\\dir".into());
+    let ok = |vars: &Vars, line| vars.parse(line).unwrap().to_string();
+    assert_eq!(
+        ok(&vars, "Type \"${bin} --version\""),
+        "Type \"vtyrec --version\""
+    );
+    assert_eq!(
+        ok(&vars, "Output ${bin}-${version}.cast"),
+        "Output vtyrec-1.2.cast"
+    );
+    assert_eq!(ok(&vars, "Type \"echo $${HOME}\""), "Type \"echo ${HOME}\"");
+    assert_eq!(ok(&vars, "Set Width ${width}"), "Set Width 120");
+    // the values aren't read as quotes or escapes
+    assert_eq!(ok(&vars, "Type \"${msg}\""), "Type \"say \\\"hi\\\"\"");
+    assert_eq!(ok(&vars, "Type ${dir}"), "Type \"C:\\\\dir\"");
+    assert_eq!(ok(&vars, "Let path '${dir}\\\\'"), "Let path C:\\dir\\");
+    assert_eq!(ok(&vars, "Type ${HOME}"), "Type \"/home/me\"");
+    assert!(vars.parse("Type ${nope}").is_err());
+    assert!(vars.parse("Type ${bin").is_err());
+    assert!(is_name("_a1") && !is_name("1a") && !is_name("a-b") && !is_name(""));
+}
@@ -0,0 +1,82 @@
+use color_eyre::Result;
+use std::time::Duration;
+
+mod cat;
+mod check;
+mod convert;
+mod fmt;
+mod fsck;
+mod grep;
+mod play;
+mod redact;
+mod transcript;
+
+#[derive(clap::Subcommand)]
+pub enum SubCommand {
+    /// Concatenate several recordings into one
+    Cat(cat::Args),
+    /// Lint tapes without running them
+    Check(check::Args),
+    /// Convert a recording between ttyrec, asciicast v1/v2 and `script`
+    Convert(convert::Args),
+    /// Rewrite tapes in the canonical form
+    Fmt(fmt::Args),
+    /// Check a ttyrec file for broken frames, and optionally repair it
+    Fsck(fsck::Args),
+    /// Find the frames where the screen matches a regex
+    Grep(grep::Args),
+    /// Replay a recording in the terminal
+    Play(play::Args),
+    /// Mask secrets like tokens and keys in a recording
+    Redact(redact::Args),
+    /// Print the text of a recording, without escape sequences
+    Transcript(transcript::Args),
+}
+impl SubCommand {
+    pub fn run(self) -> Result<()> {
+        match self {
+            SubCommand::Cat(args) => cat::run(args),
+            SubCommand::Check(args) => check::run(args),
+            SubCommand::Convert(args) => convert::run(args),
+            SubCommand::Fmt(args) => fmt::run(args),
+            SubCommand::Fsck(args) => fsck::run(args),
+            SubCommand::Grep(args) => grep::run(args),
+            SubCommand::Play(args) => play::run(args),
+            SubCommand::Redact(args) => redact::run(args),
+            SubCommand::Transcript(args) => transcript::run(args),
+        }
+    }
+}
+
+/// `500ms`/`1s`, same as `Sleep` in tapes
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    crate::parser::parse_sleep(s)
+        .map(Duration::from_millis)
+        .map_err(|_| format!("invalid duration `{s}`, make sure it's like `500ms`/`1s`"))
+}
+
+/// `24x80`, (rows, cols)
+fn parse_size(s: &str) -> Result<(u16, u16), String> {
+    s.split_once('x')
+        .and_then(|(rows, cols)| Some((rows.parse().ok()?, cols.parse().ok()?)))
+        .ok_or_else(|| format!("invalid size `{s}`, make sure it's like `24x80`"))
+}
+
+/// A multiplier above 0, like `2` or `0.5`
+fn parse_speed(s: &str) -> Result<f64, String> {
+    s.parse()
+        .ok()
+        .filter(|speed: &f64| speed.is_finite() && *speed > 0.0)
+        .ok_or_else(|| format!("invalid speed `{s}`, make sure it's a number above 0"))
+}
+
+/// `mm:ss.s`
+fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!(
+        "{:02}:{:02}.{}",
+        secs / 60,
+        secs % 60,
+        time.subsec_millis() / 100
+    )
+}
@@ -0,0 +1,122 @@
+use crate::format::{Event, Format, Frame, Recording};
+use color_eyre::Result;
+use std::{path::PathBuf, time::Duration};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Recordings to join, in order
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<PathBuf>,
+    /// Where to write the result, its format is taken from the extension
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Pause inserted between two parts, like `500ms`/`1s`
+    #[arg(short, long, value_parser = super::parse_duration, default_value = "0ms")]
+    gap: Duration,
+    /// Don't reset the screen before each part
+    #[arg(long)]
+    no_reset: bool,
+}
+
+/// `ESC c`, full reset
+const RESET: &[u8] = b"\x1bc";
+
+pub fn run(args: Args) -> Result<()> {
+    let parts = args
+        .inputs
+        .iter()
+        .map(|path| Recording::load(path, Format::from_path(path)))
+        .collect::<Result<Vec<_>>>()?;
+    join(&parts, args.gap, !args.no_reset).save(&args.output, Format::from_path(&args.output))
+}
+
+/// The header is the first part's, the timestamps of the others are moved
+/// past the one before
+fn join(parts: &[Recording], gap: Duration, reset: bool) -> Recording {
+    let mut output = Recording::default();
+    let mut offset = Duration::ZERO;
+    let mut size = None;
+    for (idx, part) in parts.iter().enumerate() {
+        if idx == 0 {
+            output.header = part.header.clone();
+        } else {
+            offset += gap;
+            if reset {
+                output.frames.push(Frame {
+                    time: offset,
+                    event: Event::Output(RESET.to_vec()),
+                });
+            }
+        }
+        // ttyrec doesn't know its size, keep the previous one then
+        if let Some((rows, cols)) = part.header.size.filter(|&s| Some(s) != size) {
+            if idx != 0 {
+                output.frames.push(Frame {
+                    time: offset,
+                    event: Event::Resize(rows, cols),
+                });
+            }
+            size = Some((rows, cols));
+        }
+        output.frames.extend(part.frames.iter().map(|frame| Frame {
+            time: offset + frame.time,
+            event: frame.event.clone(),
+        }));
+        offset += part.duration();
+    }
+    output
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    use crate::format::Header;
+    let frame = |ms, data: &str| Frame {
+        time: Duration::from_millis(ms),
+        event: Event::Output(data.as_bytes().to_vec()),
+    };
+    let part = |size, frames| Recording {
+        header: Header {
+            size,
+            ..Default::default()
+        },
+        frames,
+    };
+    let parts = [
+        part(Some((10, 40)), vec![frame(0, "a"), frame(1000, "b")]),
+        part(Some((20, 80)), vec![frame(500, "c")]),
+        // a ttyrec, the size stays
+        part(None, vec![frame(0, "d")]),
+    ];
+    let events = |rec: Recording| {
+        rec.frames
+            .into_iter()
+            .map(|f| (f.time.as_millis(), f.event.to_bytes()))
+            .collect::<Vec<_>>()
+    };
+    let joined = join(&parts, Duration::from_secs(1), true);
+    assert_eq!(joined.header.size, Some((10, 40)));
+    assert_eq!(
+        events(joined),
+        [
+            (0, b"a".to_vec()),
+            (1000, b"b".to_vec()),
+            (2000, RESET.to_vec()),
+            (2000, b"\x1b[8;20;80t".to_vec()),
+            (2500, b"c".to_vec()),
+            (3500, RESET.to_vec()),
+            (3500, b"d".to_vec()),
+        ]
+    );
+    let joined = join(&parts, Duration::ZERO, false);
+    assert_eq!(
+        events(joined),
+        [
+            (0, b"a".to_vec()),
+            (1000, b"b".to_vec()),
+            (1000, b"\x1b[8;20;80t".to_vec()),
+            (1500, b"c".to_vec()),
+            (1500, b"d".to_vec()),
+        ]
+    );
+}
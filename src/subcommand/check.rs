@@ -0,0 +1,214 @@
+use crate::parser::{Commands, Diagnostic, EnVar, Level, Located, Parser, Vars};
+use color_eyre::Result;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+/// Exits with 1 on errors, and on warnings too with `--strict`
+pub struct Args {
+    #[arg(required = true)]
+    tapes: Vec<PathBuf>,
+    /// Fail on warnings as well
+    #[arg(long)]
+    strict: bool,
+    /// Like `vtyrec --var`, for the variables which come from there
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = crate::parse_env)]
+    vars: Vec<(String, String)>,
+}
+
+/// Longer than this is probably a typo of `ms`
+const LONG_SLEEP_MS: u64 = 30_000;
+/// Commands which usually take a while, so a `Sleep` should follow them
+const LONG_RUNNING: [&str; 14] = [
+    "cargo build",
+    "cargo install",
+    "cargo test",
+    "curl",
+    "docker build",
+    "docker pull",
+    "git clone",
+    "make",
+    "npm install",
+    "pip install",
+    "pnpm install",
+    "sleep",
+    "wget",
+    "yarn",
+];
+
+pub fn run(args: Args) -> Result<()> {
+    let (mut errors, mut warnings) = (0, 0);
+    for path in &args.tapes {
+        let vars = Vars::new(args.vars.clone());
+        for diagnostic in check(path, vars)? {
+            match diagnostic.level {
+                Level::Error => errors += 1,
+                Level::Warning => warnings += 1,
+            }
+            eprintln!("{diagnostic}\n");
+        }
+    }
+    eprintln!(
+        "{} tapes checked: {errors} errors, {warnings} warnings",
+        args.tapes.len()
+    );
+    if errors > 0 || (args.strict && warnings > 0) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// The errors of [`Parser`], which follows `Source`, `Let` and `Define`,
+/// and the lints on top of them
+fn check(path: &Path, vars: Vars) -> Result<Vec<Diagnostic>> {
+    let (parser, mut diagnostics) = Parser::from_file_with_errors(path, vars)?;
+    let lint = |level, at: &Located, message: String| {
+        Diagnostic::lint(level, &at.file, at.line, &at.source, message)
+    };
+    let commands = &parser.commands;
+
+    let outputs: Vec<_> = commands
+        .iter()
+        .filter(|at| matches!(at.cmd, Commands::Output(_)))
+        .collect();
+    if let Some((last, overridden)) = outputs.split_last() {
+        for at in overridden {
+            diagnostics.push(
+                lint(Level::Warning, at, "this `Output` is never used".into())
+                    .help(format!("overridden at {}:{}", last.file, last.line)),
+            );
+        }
+    }
+
+    let last_hide = commands
+        .iter()
+        .rposition(|at| matches!(at.cmd, Commands::Hide));
+    let last_show = commands
+        .iter()
+        .rposition(|at| matches!(at.cmd, Commands::Show));
+    if let Some(hide) = last_hide.filter(|hide| last_show < Some(*hide)) {
+        diagnostics.push(
+            lint(
+                Level::Warning,
+                &commands[hide],
+                "nothing after this `Hide` is recorded".into(),
+            )
+            .help("add a `Show` where the recording should go on"),
+        );
+    }
+
+    let mut acted = false;
+    for (pos, at) in commands.iter().enumerate() {
+        match &at.cmd {
+            // `FontSize` is an error already
+            Commands::Output(_) | Commands::Set(EnVar::FontSize(_)) => (),
+            Commands::Set(_) | Commands::Env(..) if acted => diagnostics.push(
+                lint(
+                    Level::Warning,
+                    at,
+                    "settings apply to the whole tape, not from here on".into(),
+                )
+                .help("move it before the first action"),
+            ),
+            Commands::Set(_) | Commands::Env(..) => (),
+            Commands::Sleep(ms) if *ms > LONG_SLEEP_MS => {
+                acted = true;
+                diagnostics.push(
+                    lint(Level::Warning, at, format!("sleeping for {}s", ms / 1000))
+                        .help("did you mean `ms`?"),
+                );
+            }
+            Commands::Type(text, _) => {
+                acted = true;
+                let long_running = LONG_RUNNING
+                    .iter()
+                    .find(|c| text.trim_start().starts_with(*c));
+                let next = |n| commands.get(pos + n).map(|at: &Located| &at.cmd);
+                let entered = matches!(next(1), Some(Commands::Enter(..)));
+                let waited = matches!(next(2), Some(Commands::Sleep(_)));
+                if let (Some(long_running), true, false) = (long_running, entered, waited) {
+                    diagnostics.push(
+                        lint(
+                            Level::Warning,
+                            at,
+                            format!("`{long_running}` may take a while, but nothing waits for it"),
+                        )
+                        .help("add a `Sleep` after the `Enter`"),
+                    );
+                }
+            }
+            _ => acted = true,
+        }
+    }
+    // the tape itself first, then what it sources
+    let file = path.display().to_string();
+    diagnostics.sort_by_key(|d| (d.file != file, d.file.clone(), d.line));
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let dir = crate::utils::test_dir::TestDir::new("check");
+    let check = |name: &str, tape: &str, vars| check(&dir.write(name, tape), vars).unwrap();
+    let tape = "Output a.cast
+Set Width 100
+Type \"cargo build\"
+Enter
+Set Height 30
+Set FontSize 14
+Sleep 500s
+Output b.cast
+Typo
+Hide
+";
+    let found: Vec<_> = check("demo.tape", tape, Vars::default())
+        .iter()
+        .map(|d| (d.line, d.level))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (1, Level::Warning),
+            (3, Level::Warning),
+            (5, Level::Warning),
+            (6, Level::Error),
+            (7, Level::Warning),
+            (9, Level::Error),
+            (10, Level::Warning),
+        ]
+    );
+    assert!(check(
+        "fine.tape",
+        "Type \"ls\"\nEnter\nSleep 1s\n",
+        Vars::default()
+    )
+    .is_empty());
+    let tape = "Repeat 2 {\nCall menu\n}\n}\nDefine menu {\n";
+    let found: Vec<_> = check("loop.tape", tape, Vars::default())
+        .iter()
+        .map(|d| d.line)
+        .collect();
+    assert_eq!(found, [2, 4, 5]);
+    let tape = "Let w 100\nSet Width ${w}\nType \"${bin} -V\"\n";
+    assert_eq!(check("vars.tape", tape, Vars::default()).len(), 1);
+    let vars = Vars::new([("bin".to_string(), "vtyrec".to_string())]);
+    assert!(check("vars.tape", tape, vars).is_empty());
+    assert!(check(
+        "fine.tape",
+        "Hide\nType \"cd /tmp\"\nShow\n",
+        Vars::default()
+    )
+    .is_empty());
+    // what's sourced counts, and gets linted too
+    check(
+        "common.tape",
+        "Let who world\nDefine greet {\nType \"hi\"\n}\nSleep 60s\n",
+        Vars::default(),
+    );
+    let tape = "Source common.tape\nCall greet\nType \"${who}\"\nDefine greet {\n}\n";
+    let found: Vec<_> = check("main.tape", tape, Vars::default())
+        .iter()
+        .map(|d| (d.file.ends_with("common.tape"), d.line, d.level))
+        .collect();
+    assert_eq!(found, [(false, 4, Level::Error), (true, 5, Level::Warning)]);
+}
@@ -0,0 +1,35 @@
+use crate::format::{timing_path, Format, Recording};
+use color_eyre::Result;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    /// Format of <INPUT>, taken from the extension if not given
+    #[arg(long, value_enum)]
+    from: Option<Format>,
+    /// Format of <OUTPUT>, taken from the extension if not given
+    #[arg(long, value_enum)]
+    to: Option<Format>,
+    /// Timing file of a `script` input, defaults to `<INPUT>.timing`
+    #[arg(long)]
+    timing: Option<PathBuf>,
+    /// Timing file of a `script` output, defaults to `<OUTPUT>.timing`
+    #[arg(long)]
+    output_timing: Option<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let from = args.from.unwrap_or_else(|| Format::from_path(&args.input));
+    let to = args.to.unwrap_or_else(|| Format::from_path(&args.output));
+    let timing = args.timing.unwrap_or_else(|| timing_path(&args.input));
+    let output_timing = args
+        .output_timing
+        .unwrap_or_else(|| timing_path(&args.output));
+    Recording::load_with_timing(&args.input, from, timing)?.save_with_timing(
+        &args.output,
+        to,
+        output_timing,
+    )
+}
@@ -0,0 +1,215 @@
+use crate::parser::{self, Commands, Diagnostic, Diagnostics};
+use color_eyre::{eyre::WrapErr, Result};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+/// Tapes are rewritten in place
+pub struct Args {
+    #[arg(required = true)]
+    tapes: Vec<PathBuf>,
+    /// Don't write anything, exit with 1 if some tape isn't formatted
+    #[arg(long)]
+    check: bool,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let mut unformatted = 0;
+    for path in &args.tapes {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read `{}`", path.display()))?;
+        let formatted = match format(&path.display().to_string(), &text) {
+            Ok(formatted) => formatted,
+            Err(diagnostics) => {
+                eprintln!("{diagnostics}");
+                std::process::exit(1);
+            }
+        };
+        if formatted == text {
+            continue;
+        }
+        unformatted += 1;
+        if args.check {
+            println!("would reformat {}", path.display());
+        } else {
+            std::fs::write(path, formatted)?;
+            println!("reformatted {}", path.display());
+        }
+    }
+    if args.check && unformatted > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+enum Line {
+    Blank,
+    Comment(String),
+    Command(Commands),
+    /// With `${NAME}` in it, kept as is and where it is, since the `Let` it
+    /// needs may be above it
+    Verbatim(String),
+}
+
+/// `Output`, `Set` and `Env` go to the top along with the comments right
+/// above them, up to the first `Source` or line with `${NAME}`, which may set
+/// them as well. Every command is written the way it's displayed, indented
+/// inside `Repeat` and `Define`. Blank lines are kept, but never more than
+/// one in a row.
+fn format(file: &str, text: &str) -> Result<String, Diagnostics> {
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for (idx, source, cmd) in parser::parse_lines(text) {
+        let trimmed = source.trim();
+        match cmd {
+            _ if trimmed.is_empty() => lines.push(Line::Blank),
+            _ if trimmed.starts_with('#') => lines.push(Line::Comment(trimmed.to_string())),
+            _ if parser::uses_vars(trimmed) => lines.push(Line::Verbatim(trimmed.to_string())),
+            Ok(cmd) => lines.push(Line::Command(cmd)),
+            Err(e) => errors.push(Diagnostic::new(file, idx + 1, source, e)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(Diagnostics(errors));
+    }
+
+    let (mut header, mut body) = (Vec::new(), Vec::new());
+    // comments waiting to see what they're above
+    let mut comments = Vec::new();
+    // settings can't be moved above what could change them
+    let mut hoisting = true;
+    for line in lines {
+        match line {
+            Line::Comment(_) => comments.push(line),
+            Line::Command(Commands::Output(_) | Commands::Set(_) | Commands::Env(..))
+                if hoisting =>
+            {
+                header.append(&mut comments);
+                header.push(line);
+            }
+            _ => {
+                if let Line::Command(Commands::Source(_)) | Line::Verbatim(_) = line {
+                    hoisting = false;
+                }
+                body.append(&mut comments);
+                body.push(line);
+            }
+        }
+    }
+    body.append(&mut comments);
+
+    let mut out = String::new();
+    let mut blank = true;
+    // of `Repeat` and `Define`, two spaces each
+    let mut depth = 0;
+    let sections = [header, body];
+    for (idx, line) in sections.iter().flatten().enumerate() {
+        if idx == sections[0].len() && !blank {
+            out.push('\n');
+            blank = true;
+        }
+        match line {
+            Line::Blank if blank => (),
+            Line::Blank => {
+                out.push('\n');
+                blank = true;
+            }
+            Line::Comment(comment) | Line::Verbatim(comment) => {
+                out.push_str(&"  ".repeat(depth));
+                out.push_str(comment);
+                out.push('\n');
+                blank = false;
+            }
+            Line::Command(cmd) => {
+                if let Commands::End = cmd {
+                    depth = depth.saturating_sub(1);
+                }
+                out.push_str(&"  ".repeat(depth));
+                out.push_str(&cmd.to_string());
+                out.push('\n');
+                blank = false;
+                if let Commands::Repeat(_) | Commands::Define(_) = cmd {
+                    depth += 1;
+                }
+            }
+        }
+    }
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let tape = "
+# the demo
+Type@0.5s \"ls\"
+  Enter 1
+
+
+# wide
+Set Width 120
+Sleep 2000ms
+Output demo.cast
+Tab@1500ms 2
+Paste <<END
+  fn main() {}
+
+END
+Let  bin   vtyrec
+Type   \"${bin}\"
+Let h 30
+Set Height ${h}
+Repeat 2 {
+# next
+Down@100ms   1
+}
+";
+    let formatted = format("demo.tape", tape).unwrap();
+    assert_eq!(
+        formatted,
+        "# wide
+Set Width 120
+Output demo.cast
+
+# the demo
+Type@500ms \"ls\"
+Enter
+
+Sleep 2s
+Tab@1500ms 2
+Paste <<END
+  fn main() {}
+
+END
+Let bin vtyrec
+Type   \"${bin}\"
+Let h 30
+Set Height ${h}
+Repeat 2 {
+  # next
+  Down@100ms
+}
+"
+    );
+    assert_eq!(format("demo.tape", &formatted).unwrap(), formatted);
+    // still the same tape
+    let dir = crate::utils::test_dir::TestDir::new("fmt");
+    let path = dir.write("demo.tape", &formatted);
+    let parsed = parser::Parser::from_file(path, parser::Vars::default()).unwrap();
+    assert_eq!(parsed.env.size, (30, 120));
+    // what `Source` brings in runs right there
+    let tape = "Set Width 100
+
+Type \"ls\"
+Enter
+Let who world
+Source greet.tape
+Set Height 30
+Output greet.cast
+";
+    assert_eq!(format("greet.tape", tape).unwrap(), tape);
+    assert!(format("demo.tape", "Typo\n").is_err());
+    assert!(format("demo.tape", "Type <<EOF\nls\n").is_err());
+}
@@ -0,0 +1,229 @@
+use crate::format::{Compression, Encoder};
+use color_eyre::Result;
+use std::{io::Write, path::PathBuf, time::Duration};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// A ttyrec file
+    file: PathBuf,
+    /// Write a clean copy, truncated at the last good frame
+    #[arg(short, long)]
+    repair: bool,
+    /// Where to write the repaired copy, defaults to `<FILE>.fixed`
+    #[arg(short, long, requires = "repair")]
+    output: Option<PathBuf>,
+}
+
+/// sec, usec and length, all u32 in little endian
+const HEADER_LEN: usize = 12;
+/// Nothing sane writes a single frame this big
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// Exits with 1 when there's any problem, even if it got repaired.
+pub fn run(args: Args) -> Result<()> {
+    let bytes = crate::format::read_file(&args.file)?;
+    let report = scan(&bytes);
+    for (idx, problem) in &report.problems {
+        println!("{}: frame {idx} at {problem}", args.file.display());
+    }
+    println!(
+        "{}: {} good frames, {} of {} bytes",
+        args.file.display(),
+        report.frames.len(),
+        report.good_len,
+        bytes.len()
+    );
+    if args.repair {
+        let output = args.output.unwrap_or_else(|| {
+            let mut path = args.file.clone().into_os_string();
+            path.push(".fixed");
+            path.into()
+        });
+        let file = std::fs::File::create(&output)?;
+        let mut writer =
+            std::io::BufWriter::new(Encoder::new(file, Compression::from_path(&output))?);
+        for frame in &report.frames {
+            writer.write_all(&(frame.time.as_secs() as u32).to_le_bytes())?;
+            writer.write_all(&frame.time.subsec_micros().to_le_bytes())?;
+            writer.write_all(&(frame.data.len() as u32).to_le_bytes())?;
+            writer.write_all(&bytes[frame.data.clone()])?;
+        }
+        writer.flush()?;
+        writer.get_mut().finish()?;
+        println!("repaired copy written to {}", output.display());
+    }
+    if !report.problems.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+struct GoodFrame {
+    /// clamped so that it never goes backwards
+    time: Duration,
+    data: std::ops::Range<usize>,
+}
+
+enum Problem {
+    TruncatedHeader {
+        offset: usize,
+        len: usize,
+    },
+    TruncatedData {
+        offset: usize,
+        len: u32,
+        left: usize,
+    },
+    TooLong {
+        offset: usize,
+        len: u32,
+    },
+    InvalidUsec {
+        offset: usize,
+        usec: u32,
+    },
+    Backwards {
+        offset: usize,
+        prev: Duration,
+        now: Duration,
+    },
+}
+impl core::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::TruncatedHeader { offset, len } => {
+                write!(
+                    f,
+                    "offset {offset}: truncated header, only {len} of {HEADER_LEN} bytes"
+                )
+            }
+            Problem::TruncatedData { offset, len, left } => {
+                write!(
+                    f,
+                    "offset {offset}: truncated frame, {len} bytes expected but {left} left"
+                )
+            }
+            Problem::TooLong { offset, len } => {
+                write!(f, "offset {offset}: absurd frame length {len}")
+            }
+            Problem::InvalidUsec { offset, usec } => {
+                write!(f, "offset {offset}: invalid microseconds {usec}")
+            }
+            Problem::Backwards { offset, prev, now } => write!(
+                f,
+                "offset {offset}: timestamp goes backwards, {:.6}s after {:.6}s",
+                now.as_secs_f64(),
+                prev.as_secs_f64()
+            ),
+        }
+    }
+}
+
+struct Report {
+    frames: Vec<GoodFrame>,
+    /// bytes up to the end of the last good frame
+    good_len: usize,
+    /// (frame index, problem)
+    problems: Vec<(usize, Problem)>,
+}
+
+impl Report {
+    fn problem(&mut self, problem: Problem) {
+        self.problems.push((self.frames.len(), problem));
+    }
+}
+
+/// Stops at the first broken header or frame, since nothing after it can be
+/// trusted. A timestamp going backwards is reported but not fatal.
+fn scan(bytes: &[u8]) -> Report {
+    let mut report = Report {
+        frames: Vec::new(),
+        good_len: 0,
+        problems: Vec::new(),
+    };
+    let mut prev = Duration::ZERO;
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let Some(header) = bytes.get(offset..offset + HEADER_LEN) else {
+            report.problem(Problem::TruncatedHeader {
+                offset,
+                len: bytes.len() - offset,
+            });
+            break;
+        };
+        let field =
+            |idx: usize| u32::from_le_bytes(header[idx * 4..idx * 4 + 4].try_into().unwrap());
+        let (sec, usec, len) = (field(0), field(1), field(2));
+        if usec >= 1_000_000 {
+            report.problem(Problem::InvalidUsec { offset, usec });
+            break;
+        }
+        if len > MAX_FRAME_LEN {
+            report.problem(Problem::TooLong { offset, len });
+            break;
+        }
+        let data = offset + HEADER_LEN..offset + HEADER_LEN + len as usize;
+        if data.end > bytes.len() {
+            report.problem(Problem::TruncatedData {
+                offset,
+                len,
+                left: bytes.len() - data.start,
+            });
+            break;
+        }
+        let now = Duration::new(sec.into(), usec * 1000);
+        if now < prev {
+            report.problem(Problem::Backwards { offset, prev, now });
+        }
+        prev = prev.max(now);
+        offset = data.end;
+        report.good_len = offset;
+        report.frames.push(GoodFrame { time: prev, data });
+    }
+    report
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    fn frame(sec: u32, usec: u32, data: &[u8]) -> Vec<u8> {
+        [sec, usec, data.len() as u32]
+            .iter()
+            .flat_map(|n| n.to_le_bytes())
+            .chain(data.iter().copied())
+            .collect()
+    }
+    let mut bytes = [frame(1, 0, b"ab"), frame(0, 500, b"c"), frame(2, 0, b"de")].concat();
+    let report = scan(&bytes);
+    assert_eq!(report.frames.len(), 3);
+    assert!(matches!(
+        report.problems[..],
+        [(1, Problem::Backwards { offset: 14, .. })]
+    ));
+    assert_eq!(report.frames[1].time, Duration::from_secs(1));
+
+    // killed in the middle of the last frame
+    bytes.truncate(bytes.len() - 1);
+    let report = scan(&bytes);
+    assert_eq!(report.good_len, 27);
+    assert!(matches!(
+        report.problems[..],
+        [
+            _,
+            (
+                2,
+                Problem::TruncatedData {
+                    offset: 27,
+                    len: 2,
+                    left: 1
+                }
+            )
+        ]
+    ));
+
+    bytes.truncate(30);
+    assert!(matches!(
+        scan(&bytes).problems[..],
+        [_, (2, Problem::TruncatedHeader { offset: 27, len: 3 })]
+    ));
+}
@@ -0,0 +1,93 @@
+use crate::{
+    format::{Format, Recording},
+    utils::replay::Replay,
+};
+use color_eyre::Result;
+use regex::Regex;
+use std::{io::Write, path::PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Regex matched against each line of the screen
+    pattern: Regex,
+    file: PathBuf,
+    /// Format of <FILE>, taken from the extension if not given
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+    /// Terminal size like `24x80`, for recordings that don't store it
+    #[arg(long, value_parser = super::parse_size)]
+    size: Option<(u16, u16)>,
+}
+
+/// Prints `frame<TAB>time<TAB>line` for every line that starts matching, and
+/// exits with 1 when nothing matches, like grep.
+pub fn run(args: Args) -> Result<()> {
+    let rec = Recording::load(
+        &args.file,
+        args.format.unwrap_or_else(|| Format::from_path(&args.file)),
+    )?;
+    let mut replay = Replay::new(&rec, args.size, 0);
+    let mut matches = Matches::new(args.pattern);
+    let mut found = false;
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    while let Some(frame) = replay.step() {
+        for line in matches.update(replay.screen()) {
+            found = true;
+            writeln!(
+                out,
+                "{}\t{}\t{line}",
+                replay.index() - 1,
+                super::format_time(frame.time)
+            )?;
+        }
+    }
+    out.flush()?;
+    if !found {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Lines of the screen matching a regex, tracked between frames so that a
+/// line sitting on the screen is only reported once.
+pub struct Matches {
+    regex: Regex,
+    prev: Vec<String>,
+}
+impl Matches {
+    pub fn new(regex: Regex) -> Self {
+        Self {
+            regex,
+            prev: Vec::new(),
+        }
+    }
+    /// Matching lines that weren't on the previous screen
+    pub fn update(&mut self, screen: &tui_term::vt100::Screen) -> Vec<String> {
+        let now: Vec<_> = screen
+            .contents()
+            .lines()
+            .filter(|line| self.regex.is_match(line))
+            .map(str::to_string)
+            .collect();
+        let new = now
+            .iter()
+            .filter(|line| !self.prev.contains(line))
+            .cloned()
+            .collect();
+        self.prev = now;
+        new
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let mut parser = tui_term::vt100::Parser::new(3, 20, 0);
+    let mut matches = Matches::new(Regex::new("err").unwrap());
+    parser.process(b"ok\r\nerror: 1\r\n");
+    assert_eq!(matches.update(parser.screen()), ["error: 1"]);
+    parser.process(b"ok");
+    assert!(matches.update(parser.screen()).is_empty());
+    parser.process(b"\r\nerror: 2");
+    assert_eq!(matches.update(parser.screen()), ["error: 2"]);
+}
@@ -0,0 +1,201 @@
+use super::grep::Matches;
+use crate::{
+    format::{Format, Recording},
+    utils::{replay::Replay, scroll},
+};
+use color_eyre::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use regex::Regex;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+#[derive(clap::Args)]
+/// Keys: <space> pause, `/` search forward, `n` search again, `q` quit,
+/// arrows, `j`/`k`, PageUp/PageDown and `g`/`G` scroll up to a screen up.
+/// The last screen stays until `q`.
+pub struct Args {
+    file: PathBuf,
+    /// Format of <FILE>, taken from the extension if not given
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+    /// Terminal size like `24x80`, for recordings that don't store it
+    #[arg(long, value_parser = super::parse_size)]
+    size: Option<(u16, u16)>,
+    /// Playback speed multiplier
+    #[arg(long, default_value_t = 1.0, value_parser = super::parse_speed)]
+    speed: f64,
+    /// Rows of scrollback to keep
+    #[arg(long, default_value_t = scroll::DEFAULT_SCROLLBACK)]
+    scrollback: usize,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let rec = Recording::load(
+        &args.file,
+        args.format.unwrap_or_else(|| Format::from_path(&args.file)),
+    )?;
+    let mut terminal = crate::utils::cleanup::init_terminal()?;
+    let result = Player::new(&rec, &args).run(&mut terminal);
+    drop(terminal);
+    crate::utils::cleanup::restore_terminal()?;
+    result
+}
+
+struct Player<'a> {
+    rec: &'a Recording,
+    size: Option<(u16, u16)>,
+    speed: f64,
+    scrollback: usize,
+    replay: Replay<'a>,
+    /// rows up in the scrollback
+    scroll: usize,
+    /// time in the recording
+    position: Duration,
+    paused: bool,
+    /// pattern being typed after `/`
+    prompt: Option<String>,
+    search: Option<Regex>,
+    /// shown at the bottom, until the next key
+    message: Option<String>,
+}
+
+impl<'a> Player<'a> {
+    fn new(rec: &'a Recording, args: &Args) -> Self {
+        Self {
+            rec,
+            size: args.size,
+            speed: args.speed,
+            scrollback: args.scrollback,
+            replay: Replay::new(rec, args.size, args.scrollback),
+            scroll: 0,
+            position: Duration::ZERO,
+            paused: false,
+            prompt: None,
+            search: None,
+            message: None,
+        }
+    }
+
+    fn run(mut self, terminal: &mut ratatui::DefaultTerminal) -> Result<()> {
+        let mut last = Instant::now();
+        loop {
+            while self.replay.peek().is_some_and(|f| f.time <= self.position) {
+                self.replay.step();
+            }
+            // the last screen stays until `q`
+            terminal.draw(|f| self.ui(f))?;
+
+            let wait = match self.replay.peek() {
+                Some(next) => next
+                    .time
+                    .saturating_sub(self.position)
+                    .div_f64(self.speed)
+                    .min(Duration::from_millis(100)),
+                None => Duration::from_millis(100),
+            };
+            if event::poll(wait)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.on_key(key.code) {
+                        return Ok(());
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if !self.paused && self.prompt.is_none() && self.replay.peek().is_some() {
+                self.position += now.duration_since(last).mul_f64(self.speed);
+            }
+            last = now;
+        }
+    }
+
+    /// `false` to quit
+    fn on_key(&mut self, code: KeyCode) -> bool {
+        self.message = None;
+        if let Some(prompt) = &mut self.prompt {
+            match code {
+                KeyCode::Char(ch) => prompt.push(ch),
+                KeyCode::Backspace => {
+                    prompt.pop();
+                }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Enter => {
+                    match Regex::new(prompt) {
+                        Ok(regex) => {
+                            self.search = Some(regex);
+                            self.search_next();
+                        }
+                        Err(e) => self.message = Some(e.to_string()),
+                    }
+                    self.prompt = None;
+                }
+                _ => (),
+            }
+            return true;
+        }
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('/') => self.prompt = Some(String::new()),
+            KeyCode::Char('n') => self.search_next(),
+            code => {
+                let rows = self.replay.screen().size().0;
+                if let Some(rows) = scroll::on_key(code, self.scroll, rows) {
+                    self.scroll = self.replay.set_scrollback(rows);
+                }
+            }
+        }
+        true
+    }
+
+    /// Seek to the next frame where a new line matches, stay put if none
+    fn search_next(&mut self) {
+        let Some(regex) = &self.search else {
+            return;
+        };
+        // frames can't be undone, so search on a copy
+        let mut probe = Replay::new(self.rec, self.size, self.scrollback);
+        while probe.index() < self.replay.index() {
+            probe.step();
+        }
+        let mut matches = Matches::new(regex.clone());
+        matches.update(probe.screen());
+        while let Some(frame) = probe.step() {
+            if !matches.update(probe.screen()).is_empty() {
+                self.replay = probe;
+                self.scroll = 0;
+                self.position = frame.time;
+                return;
+            }
+        }
+        self.message = Some(format!("Pattern not found: {regex}"));
+    }
+
+    fn ui(&self, f: &mut ratatui::Frame) {
+        use ratatui::{
+            layout::{Constraint, Layout},
+            widgets::Paragraph,
+        };
+        use tui_term::widget::PseudoTerminal;
+        let bottom = match (&self.prompt, &self.message) {
+            (Some(prompt), _) => Some(format!("/{prompt}")),
+            (None, Some(message)) => Some(message.clone()),
+            (None, None) => None,
+        };
+        let screen = match bottom {
+            Some(bottom) => {
+                let [screen, line] =
+                    Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(f.area());
+                f.render_widget(Paragraph::new(bottom), line);
+                screen
+            }
+            None => f.area(),
+        };
+        f.render_widget(PseudoTerminal::new(self.replay.screen()), screen);
+        if self.scroll > 0 {
+            scroll::indicator(f, screen, self.scroll);
+        }
+    }
+}
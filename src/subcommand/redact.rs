@@ -0,0 +1,95 @@
+use crate::{
+    format::{Event, Format, Recording},
+    utils::redact::Redactor,
+};
+use color_eyre::{eyre::bail, Result};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    /// Format of <INPUT>, taken from the extension if not given
+    #[arg(long, value_enum)]
+    from: Option<Format>,
+    /// Format of <OUTPUT>, taken from the extension if not given
+    #[arg(long, value_enum)]
+    to: Option<Format>,
+    /// More patterns, one regex per line
+    #[arg(short, long)]
+    rules: Option<PathBuf>,
+    /// Only use the patterns from <RULES>
+    #[arg(long, requires = "rules")]
+    no_builtin: bool,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let from = args.from.unwrap_or_else(|| Format::from_path(&args.input));
+    let to = args.to.unwrap_or_else(|| Format::from_path(&args.output));
+    let mut redactor = match args.no_builtin {
+        true => Redactor::default(),
+        false => Redactor::builtin(),
+    };
+    if let Some(rules) = &args.rules {
+        redactor.add_rules(rules)?;
+    }
+    if redactor.is_empty() {
+        bail!("No patterns to redact");
+    }
+    let mut rec = Recording::load(&args.input, from)?;
+    let count = redactor.redact_header(&mut rec.header) + redact(&redactor, &mut rec);
+    rec.save(&args.output, to)?;
+    println!("{count} secrets masked");
+    Ok(())
+}
+
+/// The output is joined first, so a secret split across frames is found as
+/// long as nothing else is printed in between
+fn redact(redactor: &Redactor, rec: &mut Recording) -> usize {
+    let mut outputs: Vec<_> = rec
+        .frames
+        .iter_mut()
+        .filter_map(|frame| match &mut frame.event {
+            Event::Output(data) => Some(data),
+            Event::Resize(..) => None,
+        })
+        .collect();
+    let mut joined = outputs
+        .iter()
+        .flat_map(|data| data.iter().copied())
+        .collect::<Vec<_>>();
+    let count = redactor.redact(&mut joined);
+    // same length, so it splits back at the same places
+    let mut rest = &joined[..];
+    for data in &mut outputs {
+        let (head, tail) = rest.split_at(data.len());
+        data.copy_from_slice(head);
+        rest = tail;
+    }
+    count
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    use crate::format::{Frame, Header};
+    use std::time::Duration;
+    let frame = |data: &[u8]| Frame {
+        time: Duration::ZERO,
+        event: Event::Output(data.to_vec()),
+    };
+    let mut rec = Recording {
+        header: Header::default(),
+        frames: vec![frame(b"AKIAIOSF"), frame(b"ODNN7EXAMPLE\r\n"), frame(b"$ ")],
+    };
+    assert_eq!(redact(&Redactor::builtin(), &mut rec), 1);
+    let data: Vec<_> = rec
+        .frames
+        .iter()
+        .map(|f| match &f.event {
+            Event::Output(data) => data.clone(),
+            Event::Resize(..) => unreachable!(),
+        })
+        .collect();
+    assert_eq!(data, [&b"********"[..], b"************\r\n", b"$ "]);
+}
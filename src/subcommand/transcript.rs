@@ -0,0 +1,248 @@
+use crate::format::{Event, Format, Recording, DEFAULT_SIZE};
+use color_eyre::Result;
+use std::{collections::HashMap, io::Write, path::PathBuf, time::Duration};
+use tui_term::vt100;
+
+#[derive(clap::Args)]
+pub struct Args {
+    file: PathBuf,
+    /// Format of <FILE>, taken from the extension if not given
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+    /// Terminal size like `24x80`, for recordings that don't store it
+    #[arg(long, value_parser = super::parse_size)]
+    size: Option<(u16, u16)>,
+    /// Prefix each line with the time it showed up
+    #[arg(short, long)]
+    timestamps: bool,
+    /// Write a Markdown document instead of plain text
+    #[arg(short, long)]
+    markdown: bool,
+}
+
+/// Sequences that wipe the screen without scrolling it into the scrollback
+const CLEARS: [&[u8]; 3] = [b"\x1b[2J", b"\x1b[3J", b"\x1bc"];
+
+pub fn run(args: Args) -> Result<()> {
+    let rec = Recording::load(
+        &args.file,
+        args.format.unwrap_or_else(|| Format::from_path(&args.file)),
+    )?;
+    let lines = collect(&rec, args.size);
+
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    let fence = if args.markdown {
+        let title = rec.header.title.clone().unwrap_or_else(|| {
+            args.file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        // longer than any backtick run in the content
+        let longest = lines
+            .iter()
+            .flat_map(|(_, line)| line.split(|c| c != '`'))
+            .map(str::len)
+            .max()
+            .unwrap_or_default();
+        let fence = "`".repeat(longest.max(2) + 1);
+        writeln!(out, "# {title}\n\n{fence}console")?;
+        Some(fence)
+    } else {
+        None
+    };
+    for (time, line) in lines {
+        if args.timestamps {
+            write!(out, "[{}] ", super::format_time(time))?;
+        }
+        writeln!(out, "{line}")?;
+    }
+    if let Some(fence) = fence {
+        writeln!(out, "{fence}")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Rows kept while reading a recording, the parser is started over from its
+/// screen before it fills up
+const SCROLLBACK: usize = 1000;
+
+/// Lines that scrolled by or got cleared, followed by the final screen, each
+/// with the time its content first showed up.
+fn collect(rec: &Recording, size: Option<(u16, u16)>) -> Vec<(Duration, String)> {
+    let (rows, cols) = size.or(rec.header.size).unwrap_or(DEFAULT_SIZE);
+    let mut parser = vt100::Parser::new(rows, cols, SCROLLBACK);
+    let mut transcript = Transcript::default();
+    // rows of the main screen after the previous frame
+    let mut prev_rows = Vec::new();
+    let mut prev_scrollback = 0;
+    for frame in &rec.frames {
+        let data = match &frame.event {
+            Event::Output(data) => data.as_slice(),
+            Event::Resize(rows, cols) => {
+                parser.set_size(*rows, *cols);
+                &[]
+            }
+        };
+        // rows scrolled out by the whole frame
+        let mut scrolled = 0;
+        let (_, cols) = parser.screen().size();
+        // vt100 only shows up to a screen of its scrollback, so a line or a
+        // row at a time, which scrolls at most two rows
+        let chunks = data
+            .split_inclusive(|b| *b == b'\n')
+            .flat_map(|line| line.chunks(usize::from(cols.max(1))));
+        for chunk in chunks {
+            parser.process(chunk);
+            if parser.screen().alternate_screen() {
+                continue;
+            }
+            parser.set_scrollback(usize::MAX);
+            let scrollback = parser.screen().scrollback();
+            let (rows, _) = parser.screen().size();
+            let new = scrollback
+                .saturating_sub(prev_scrollback)
+                .min(usize::from(rows));
+            // oldest first
+            parser.set_scrollback(new);
+            visible_rows(parser.screen())
+                .take(new)
+                .for_each(|row| transcript.push(row, frame.time));
+            parser.set_scrollback(0);
+            scrolled += new;
+            prev_scrollback = scrollback;
+            if scrollback > SCROLLBACK / 2 {
+                let state = parser.screen().state_formatted();
+                parser = vt100::Parser::new(rows, cols, SCROLLBACK);
+                parser.process(&state);
+                prev_scrollback = 0;
+            }
+        }
+        if parser.screen().alternate_screen() {
+            // full screen programs, only what they leave behind matters
+            continue;
+        }
+        let cleared =
+            matches!(&frame.event, Event::Output(data) if CLEARS.iter().any(|c| contains(data, c)));
+        if cleared {
+            prev_rows
+                .into_iter()
+                .skip(scrolled)
+                .for_each(|row| transcript.push(row, frame.time));
+        }
+        prev_rows = visible_rows(parser.screen()).collect();
+        transcript.seen(&prev_rows, frame.time);
+    }
+    let end = rec.duration();
+    visible_rows(parser.screen()).for_each(|row| transcript.push(row, end));
+    transcript.finish()
+}
+
+/// (text, wrapped into the next row)
+fn visible_rows(screen: &vt100::Screen) -> impl Iterator<Item = (String, bool)> + '_ {
+    let (_, cols) = screen.size();
+    screen
+        .rows(0, cols)
+        .enumerate()
+        .map(|(idx, row)| (row, screen.row_wrapped(idx as u16)))
+}
+
+fn contains(data: &[u8], pat: &[u8]) -> bool {
+    data.windows(pat.len()).any(|w| w == pat)
+}
+
+#[derive(Default)]
+struct Transcript {
+    first_seen: HashMap<String, Duration>,
+    /// rows of a wrapped line
+    pending: String,
+    pending_time: Option<Duration>,
+    lines: Vec<(Duration, String)>,
+}
+impl Transcript {
+    fn seen(&mut self, rows: &[(String, bool)], time: Duration) {
+        for (row, _) in rows {
+            self.first_seen.entry(row.clone()).or_insert(time);
+        }
+    }
+    /// `now` is used for rows that never made it into a snapshot
+    fn push(&mut self, (row, wrapped): (String, bool), now: Duration) {
+        let time = self.first_seen.get(&row).copied().unwrap_or(now);
+        self.pending_time.get_or_insert(time);
+        self.pending.push_str(&row);
+        if wrapped {
+            return;
+        }
+        let line = std::mem::take(&mut self.pending).trim_end().to_string();
+        let time = self.pending_time.take().unwrap_or_default();
+        match self.lines.last() {
+            // a redrawn line or a run of blank lines
+            Some((_, last)) if *last == line => (),
+            None if line.is_empty() => (),
+            _ => self.lines.push((time, line)),
+        }
+    }
+    fn finish(mut self) -> Vec<(Duration, String)> {
+        if let Some(time) = self.pending_time {
+            self.push((String::new(), false), time);
+        }
+        while self.lines.last().is_some_and(|(_, line)| line.is_empty()) {
+            self.lines.pop();
+        }
+        self.lines
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    use crate::format::{Frame, Header};
+    let frame = |ms, data: &str| Frame {
+        time: Duration::from_millis(ms),
+        event: Event::Output(data.as_bytes().to_vec()),
+    };
+    let rec = Recording {
+        header: Header {
+            size: Some((3, 10)),
+            ..Default::default()
+        },
+        frames: vec![
+            frame(0, "$ ls\r\n"),
+            frame(100, "a\r\nb\r\nc\r\n"),
+            frame(200, "$ clear"),
+            frame(300, "\x1b[H\x1b[2J$ echo 0123456789abc\r\n"),
+        ],
+    };
+    let lines: Vec<_> = collect(&rec, None)
+        .into_iter()
+        .map(|(time, line)| (time.as_millis(), line))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (0, "$ ls".to_string()),
+            (100, "a".to_string()),
+            (100, "b".to_string()),
+            (100, "c".to_string()),
+            (200, "$ clear".to_string()),
+            (300, "$ echo 0123456789abc".to_string()),
+        ]
+    );
+    // one frame scrolling out far more than the 3 rows, like `cat`
+    let lines: String = (0..2000).map(|n| format!("line {n}\r\n")).collect();
+    let rec = Recording {
+        frames: vec![frame(0, &lines), frame(100, "$ ")],
+        ..rec
+    };
+    let lines: Vec<_> = collect(&rec, None)
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect();
+    let expected: Vec<_> = (0..2000)
+        .map(|n| format!("line {n}"))
+        .chain(["$".to_string()])
+        .collect();
+    assert_eq!(lines, expected);
+}
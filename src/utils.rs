@@ -1,3 +1,11 @@
 pub mod child;
+pub mod cleanup;
+pub mod hotkey;
 pub mod key2bytes;
 pub mod recorder;
+pub mod redact;
+pub mod replay;
+pub mod scroll;
+pub mod status;
+#[cfg(test)]
+pub mod test_dir;
@@ -1,47 +1,124 @@
 use crate::utils::recorder::VtyParser;
-use portable_pty::{CommandBuilder, SlavePty};
-use std::sync::Once;
+use portable_pty::{Child, ChildKiller, CommandBuilder, SlavePty};
+use std::{sync::mpsc, time::Duration};
+use tokio::sync::oneshot;
 
-pub static CHILD_SHOULD_EXIT: Once = Once::new();
-pub static CHILD_HAD_EXIT: Once = Once::new();
+/// How long to keep reading after the child exited, in case it left
+/// background processes holding the pty
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
 
+pub struct PtyChild {
+    /// Resolves to the code a shell would give once the child exited and its
+    /// output was read, `128 + signal` if it got killed
+    pub exit: oneshot::Receiver<i32>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+}
+impl PtyChild {
+    /// Send a SIGHUP, [`PtyChild::exit`] resolves once it's gone
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        self.killer.kill()
+    }
+}
+
+/// The output is fed into `parser` by a reader thread until EOF, while
+/// another thread waits for the child to exit.
 pub fn spawn_pty_child(
     cmd: CommandBuilder,
-    mut rdr: Box<dyn std::io::Read + Send>,
+    rdr: Box<dyn std::io::Read + Send>,
     slave: Box<dyn SlavePty + Send>,
     parser: VtyParser,
-) {
-    let mut child = slave.spawn_command(cmd).unwrap();
-    let _join = std::thread::spawn(move || {
-        // Consume the output from the child
-        // Can't read the full buffer, since that would wait for EOF
-        let mut buf = [0u8; 8192];
-        let mut processed_buf = Vec::with_capacity(8192);
-        loop {
-            if CHILD_SHOULD_EXIT.is_completed() {
-                break;
-            } else if let Some(_s) = child.try_wait().transpose() {
-                break;
-            } else {
-                // A known issue:
-                // `reader read` will block this thread, which
-                // blocks `child try_wait`, causing oneshot send
-                // after `reader read` finish, leading program
-                // to shutdown after another press after `exit`
-                let size = rdr.read(&mut buf).unwrap();
-                if size > 0 {
-                    processed_buf.extend_from_slice(&buf[..size]);
-                    let mut parser = parser.write().unwrap();
-                    parser.process(&processed_buf);
-
-                    // Clear the processed portion of the buffer
-                    processed_buf.clear();
-                } else {
-                    break;
-                }
-            }
-        }
-        CHILD_HAD_EXIT.call_once(|| ());
-        drop(slave);
+) -> color_eyre::Result<PtyChild> {
+    let mut child = slave
+        .spawn_command(cmd)
+        .map_err(|e| color_eyre::eyre::eyre!("Failed to spawn the child: {e:#}"))?;
+    // the child has its own copy, ours would keep the pty open after it exits
+    drop(slave);
+    super::cleanup::set_child(child.clone_killer());
+    let killer = child.clone_killer();
+
+    let (eof_tx, eof_rx) = mpsc::channel::<()>();
+    std::thread::spawn(move || read_output(rdr, parser, eof_tx));
+
+    let (exit_tx, exit_rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        let status = wait(&mut child).unwrap_or_else(|e| {
+            tracing::warn!("Failed to wait for the child: {e}");
+            1
+        });
+        // Err either way, disconnected on EOF or timeout
+        let _ = eof_rx.recv_timeout(DRAIN_TIMEOUT);
+        let _ = exit_tx.send(status);
     });
+    Ok(PtyChild {
+        exit: exit_rx,
+        killer,
+    })
+}
+
+/// `_eof` gets dropped when the pty is closed
+fn read_output(mut rdr: Box<dyn std::io::Read + Send>, parser: VtyParser, _eof: mpsc::Sender<()>) {
+    let mut buf = [0u8; 8192];
+    loop {
+        match rdr.read(&mut buf) {
+            Ok(0) => break,
+            Ok(size) => parser.write().unwrap().process(&buf[..size]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            // linux gives EIO instead of EOF once the child side is closed
+            Err(_) => break,
+        }
+    }
+}
+
+/// Waits for the child itself on unix, as portable-pty only keeps the
+/// description of a signal and not its number
+#[cfg(unix)]
+fn wait(child: &mut Box<dyn Child + Send + Sync>) -> std::io::Result<i32> {
+    let Some(pid) = child.process_id() else {
+        return child.wait().map(|status| status.exit_code() as i32);
+    };
+    let mut status = 0;
+    loop {
+        // SAFETY: the pid is our own child, not waited for by anyone else
+        if unsafe { libc::waitpid(pid as libc::pid_t, &mut status, 0) } != -1 {
+            return Ok(exit_code(status));
+        }
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+#[cfg(not(unix))]
+fn wait(child: &mut Box<dyn Child + Send + Sync>) -> std::io::Result<i32> {
+    child.wait().map(|status| status.exit_code() as i32)
+}
+
+/// The code a shell would give for a raw wait status, `128 + signal` if it
+/// got killed
+#[cfg(unix)]
+fn exit_code(status: i32) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    let status = std::process::ExitStatus::from_raw(status);
+    match status.signal() {
+        Some(signal) => 128 + signal,
+        None => status.code().unwrap_or(1),
+    }
+}
+
+#[cfg(all(test, unix))]
+#[test]
+fn test() {
+    let code = |script: &str| {
+        let mut child: Box<dyn Child + Send + Sync> = Box::new(
+            std::process::Command::new("sh")
+                .args(["-c", script])
+                .spawn()
+                .unwrap(),
+        );
+        wait(&mut child).unwrap()
+    };
+    assert_eq!(code("exit 0"), 0);
+    assert_eq!(code("exit 3"), 3);
+    assert_eq!(code("kill -TERM $$"), 128 + libc::SIGTERM);
+    assert_eq!(code("kill -KILL $$"), 128 + libc::SIGKILL);
 }
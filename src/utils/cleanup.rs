@@ -0,0 +1,104 @@
+//! Leave things in a sane state when vtyrec goes down on a signal, a panic
+//! or an error
+use crate::utils::recorder::RecFile;
+use portable_pty::ChildKiller;
+use std::sync::{Mutex, PoisonError};
+
+struct Pending {
+    recording: Option<RecFile>,
+    child: Option<Box<dyn ChildKiller + Send + Sync>>,
+    terminal: bool,
+}
+static PENDING: Mutex<Pending> = Mutex::new(Pending {
+    recording: None,
+    child: None,
+    terminal: false,
+});
+
+fn pending() -> std::sync::MutexGuard<'static, Pending> {
+    // a panic while holding the lock must not stop the cleanup
+    PENDING.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Install the error report hooks, and a panic hook which cleans up before
+/// reporting and then exits, since a panic in the child thread would
+/// otherwise leave the main thread running.
+pub fn install() -> color_eyre::Result<()> {
+    let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::new().into_hooks();
+    eyre_hook.install()?;
+    let panic_hook = panic_hook.into_panic_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        run();
+        panic_hook(info);
+        std::process::exit(101);
+    }));
+    Ok(())
+}
+
+/// [`ratatui::try_init`], which also starts watching for SIGTERM, SIGHUP and
+/// SIGINT so that the terminal gets restored on them.
+pub fn init_terminal() -> std::io::Result<ratatui::DefaultTerminal> {
+    let terminal = ratatui::try_init()?;
+    pending().terminal = true;
+    std::thread::spawn(watch_signals);
+    Ok(terminal)
+}
+pub fn restore_terminal() -> std::io::Result<()> {
+    pending().terminal = false;
+    ratatui::try_restore()
+}
+/// The recording gets flushed and synced to disk
+pub fn set_recording(file: RecFile) {
+    pending().recording = Some(file);
+}
+/// The child gets a SIGHUP
+pub fn set_child(killer: Box<dyn ChildKiller + Send + Sync>) {
+    pending().child = Some(killer);
+}
+
+/// Best effort, errors are only reported since we are going down anyway.
+/// A compressed recording gets its trailer.
+pub fn run() {
+    let mut pending = pending();
+    if let Some(file) = pending.recording.take() {
+        if let Err(e) = file.finish() {
+            eprintln!("Failed to save the recording: {e}");
+        }
+    }
+    if std::mem::take(&mut pending.terminal) {
+        let _ = ratatui::try_restore();
+    }
+    if let Some(mut child) = pending.child.take() {
+        let _ = child.kill();
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn watch_signals() {
+    #[cfg(unix)]
+    let signal = {
+        use tokio::signal::unix::{signal, SignalKind};
+        let (Ok(mut term), Ok(mut hup), Ok(mut int)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::hangup()),
+            signal(SignalKind::interrupt()),
+        ) else {
+            tracing::warn!("Failed to watch signals");
+            return;
+        };
+        tokio::select! {
+            _ = term.recv() => 15,
+            _ = hup.recv() => 1,
+            _ = int.recv() => 2,
+        }
+    };
+    #[cfg(not(unix))]
+    let signal = {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        2
+    };
+    run();
+    std::process::exit(128 + signal);
+}
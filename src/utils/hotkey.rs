@@ -0,0 +1,66 @@
+//! The prefix key of vtyrec's own hotkeys, which is not sent to the child
+//! unless pressed twice, like tmux's
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+pub const DEFAULT_PREFIX: &str = "ctrl-\\";
+/// Pressed after the prefix
+pub const PAUSE: KeyCode = KeyCode::Char('p');
+/// Pressed after the prefix, browse the scrollback until `q` or Esc
+pub const SCROLL: KeyCode = KeyCode::Char('[');
+
+#[derive(Clone)]
+pub struct Prefix {
+    code: KeyCode,
+    /// what the child gets when it's pressed twice
+    byte: u8,
+}
+impl Prefix {
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        key.code == self.code && key.modifiers == KeyModifiers::CONTROL
+    }
+    pub fn byte(&self) -> u8 {
+        self.byte
+    }
+}
+
+/// Only `ctrl-<key>`, where the key is a letter or one of `\]^_`. The
+/// letters which are also Tab, Enter and Backspace can't be told apart.
+impl core::str::FromStr for Prefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("`{s}` is not a hotkey, try something like `ctrl-a` or `ctrl-\\`");
+        let key = s
+            .strip_prefix("ctrl-")
+            .or_else(|| s.strip_prefix("C-"))
+            .ok_or_else(err)?;
+        let mut chars = key.chars();
+        let (Some(ch), None) = (chars.next(), chars.next()) else {
+            return Err(err());
+        };
+        let ch = ch.to_ascii_lowercase();
+        let byte = ch.to_ascii_uppercase() as u8 & 0x1f;
+        let code = match ch {
+            'h' | 'i' | 'j' | 'm' => return Err(err()),
+            'a'..='z' => KeyCode::Char(ch),
+            // crossterm reports 0x1c..=0x1f as ctrl + 4..=7
+            '\\' | ']' | '^' | '_' => KeyCode::Char((b'4' + byte - 0x1c) as char),
+            _ => return Err(err()),
+        };
+        Ok(Self { code, byte })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    let prefix: Prefix = DEFAULT_PREFIX.parse().unwrap();
+    assert_eq!(prefix.byte(), 0x1c);
+    assert!(prefix.matches(&KeyEvent::new(KeyCode::Char('4'), KeyModifiers::CONTROL)));
+    let prefix: Prefix = "C-B".parse().unwrap();
+    assert_eq!(prefix.byte(), 2);
+    assert!(prefix.matches(&KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL)));
+    assert!(!prefix.matches(&KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE)));
+    assert!("ctrl-m".parse::<Prefix>().is_err());
+    assert!("alt-x".parse::<Prefix>().is_err());
+}
@@ -0,0 +1,46 @@
+use crate::format::{Event, Frame, Recording, DEFAULT_SIZE};
+use tui_term::vt100;
+
+/// Feed the frames of a recording into a [`vt100::Parser`] one by one
+pub struct Replay<'a> {
+    frames: std::slice::Iter<'a, Frame>,
+    parser: vt100::Parser,
+    /// number of frames applied
+    index: usize,
+}
+
+impl<'a> Replay<'a> {
+    /// `size` overrides the size stored in the recording
+    pub fn new(rec: &'a Recording, size: Option<(u16, u16)>, scrollback: usize) -> Self {
+        let (rows, cols) = size.or(rec.header.size).unwrap_or(DEFAULT_SIZE);
+        Self {
+            frames: rec.frames.iter(),
+            parser: vt100::Parser::new(rows, cols, scrollback),
+            index: 0,
+        }
+    }
+    /// Apply the next frame and return it, `None` when the recording ends
+    pub fn step(&mut self) -> Option<&'a Frame> {
+        let frame = self.frames.next()?;
+        match &frame.event {
+            Event::Output(data) => self.parser.process(data),
+            Event::Resize(rows, cols) => self.parser.set_size(*rows, *cols),
+        }
+        self.index += 1;
+        Some(frame)
+    }
+    /// The frame [`Replay::step`] would apply next
+    pub fn peek(&self) -> Option<&'a Frame> {
+        self.frames.as_slice().first()
+    }
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn screen(&self) -> &vt100::Screen {
+        self.parser.screen()
+    }
+    /// See [`crate::utils::scroll::set_offset`]
+    pub fn set_scrollback(&mut self, rows: usize) -> usize {
+        crate::utils::scroll::set_offset(&mut self.parser, rows)
+    }
+}
@@ -0,0 +1,75 @@
+//! Browsing the scrollback, shared by recording and `play`
+use crossterm::event::KeyCode;
+use ratatui::{
+    layout::Rect,
+    style::{Style, Stylize},
+    widgets::Paragraph,
+};
+use tui_term::vt100;
+
+pub const DEFAULT_SCROLLBACK: usize = 1000;
+
+/// The new offset from the bottom for a scroll key, `None` if it's not one.
+/// `page` is the height of the screen.
+pub fn on_key(code: KeyCode, offset: usize, page: u16) -> Option<usize> {
+    let page = usize::from(page.max(1));
+    Some(match code {
+        KeyCode::Up | KeyCode::Char('k') => offset.saturating_add(1),
+        KeyCode::Down | KeyCode::Char('j') => offset.saturating_sub(1),
+        KeyCode::PageUp => offset.saturating_add(page),
+        KeyCode::PageDown => offset.saturating_sub(page),
+        // clamped by `set_offset`
+        KeyCode::Home | KeyCode::Char('g') => usize::MAX,
+        KeyCode::End | KeyCode::Char('G') => 0,
+        _ => return None,
+    })
+}
+
+/// Scroll `parser` up `offset` rows, returns how far up it ended up. vt100
+/// only shows up to a screen of its scrollback, it underflows past that.
+pub fn set_offset(parser: &mut vt100::Parser, offset: usize) -> usize {
+    let (rows, _) = parser.screen().size();
+    parser.set_scrollback(offset.min(usize::from(rows)));
+    parser.screen().scrollback()
+}
+
+/// `[offset]` at the top right of `area`, like tmux's copy mode
+pub fn indicator(f: &mut ratatui::Frame, area: Rect, offset: usize) {
+    let text = format!("[{offset}]");
+    let width = (text.len() as u16).min(area.width);
+    let area = Rect {
+        x: area.right() - width,
+        width,
+        height: area.height.min(1),
+        ..area
+    };
+    f.render_widget(
+        Paragraph::new(text).style(Style::new().black().on_yellow()),
+        area,
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    assert_eq!(on_key(KeyCode::Up, 0, 24), Some(1));
+    assert_eq!(on_key(KeyCode::PageDown, 10, 24), Some(0));
+    assert_eq!(on_key(KeyCode::Char('x'), 3, 24), None);
+    let mut parser = vt100::Parser::new(3, 10, 100);
+    parser.process(
+        (0..20)
+            .map(|n| format!("{n}\r\n"))
+            .collect::<String>()
+            .as_bytes(),
+    );
+    for (code, offset) in [
+        (KeyCode::PageUp, 0),
+        (KeyCode::PageUp, 3),
+        (KeyCode::Char('g'), 0),
+    ] {
+        let offset = set_offset(&mut parser, on_key(code, offset, 3).unwrap());
+        assert_eq!(offset, 3);
+        assert_eq!(parser.screen().contents(), "15\n16\n17");
+        assert!(!parser.screen().contents_formatted().is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+//! The line below the terminal while recording, which is not recorded
+use crate::utils::recorder::VtyrecWriter;
+use ratatui::{
+    style::{Style, Stylize},
+    text::{Line, Span},
+};
+
+pub struct StatusBar {
+    file: String,
+}
+impl StatusBar {
+    pub fn new(file: &std::ffi::OsStr) -> Self {
+        Self {
+            file: file.to_string_lossy().into_owned(),
+        }
+    }
+    pub fn line(&self, rec: &VtyrecWriter) -> Line<'static> {
+        let state = if rec.paused() {
+            Span::styled(" PAUSED ", Style::new().black().on_yellow())
+        } else {
+            Span::styled(" REC ", Style::new().white().on_red().bold())
+        };
+        let secs = rec.elapsed().as_secs();
+        Line::from(vec![
+            state,
+            Span::raw(format!(
+                " {:02}:{:02}:{:02}  {}  {} frames  {}",
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60,
+                self.file,
+                rec.frames(),
+                human_bytes(rec.bytes())
+            )),
+        ])
+        .on_dark_gray()
+    }
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test() {
+    assert_eq!(human_bytes(1000), "1000 B");
+    assert_eq!(human_bytes(1536), "1.5 KiB");
+    assert_eq!(human_bytes(3 << 30), "3.0 GiB");
+}
@@ -0,0 +1,35 @@
+//! A directory of its own for each test, removed with it
+
+use std::path::{Path, PathBuf};
+
+/// `vtyrec-<pid>-<name>` in the temp dir, so that runs at the same time
+/// don't share it
+pub struct TestDir(PathBuf);
+impl TestDir {
+    /// `name` only has to be unique among the tests
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("vtyrec-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+    /// Writes `contents` to `name` in the directory, and gives its path
+    pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.0.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+}
+impl std::ops::Deref for TestDir {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}